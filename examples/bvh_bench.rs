@@ -0,0 +1,71 @@
+// times closest hit queries over the make_spheres grid scaled up to 100k spheres, once with
+// a plain linear scan and once through the bvh
+//
+//     cargo run --release --example bvh_bench
+use raytracer::{
+    material::{Lambertian, Material},
+    object::{Hittable, Sphere},
+    rand::Rand,
+    ray3::Ray3,
+    scene::Scene,
+    vec3::{Color3, Point3},
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+const GRID: i32 = 316; // 316 * 316 ~= 100k
+const RAYS: usize = 2_000;
+
+fn main() {
+    // one material shared by every sphere
+    let material: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 1.0)));
+    let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+    for j in -GRID / 2..GRID / 2 {
+        for i in -GRID / 2..GRID / 2 {
+            objects.push(Box::new(Sphere::new(
+                Point3::new(j as f64, -4.0, i as f64),
+                0.5,
+                material.clone(),
+            )));
+        }
+    }
+
+    let build_start = Instant::now();
+    let scene = Scene::new(objects);
+    println!("built bvh over {} spheres in {:?}", scene.len(), build_start.elapsed());
+
+    let mut randomizer = Rand::new();
+    let rays: Vec<Ray3> = (0..RAYS)
+        .map(|_| {
+            let target = Point3::new(
+                randomizer.next_with_range(-(GRID as f64) / 2.0, GRID as f64 / 2.0),
+                -4.0,
+                randomizer.next_with_range(-(GRID as f64) / 2.0, GRID as f64 / 2.0),
+            );
+            let origin = Point3::new(0.0, 20.0, 0.0);
+            Ray3::new(origin, target - origin)
+        })
+        .collect();
+
+    let linear_start = Instant::now();
+    let linear_hits = rays.iter().filter(|ray| scene.hit_linear(ray, 0.001, f64::INFINITY).is_some()).count();
+    let linear_time = linear_start.elapsed();
+
+    let bvh_start = Instant::now();
+    let bvh_hits = rays.iter().filter(|ray| scene.hit(ray, 0.001, f64::INFINITY).is_some()).count();
+    let bvh_time = bvh_start.elapsed();
+
+    // same answers or the comparison means nothing
+    for ray in &rays {
+        let linear_t = scene.hit_linear(ray, 0.001, f64::INFINITY).map(|hit| hit.t);
+        let bvh_t = scene.hit(ray, 0.001, f64::INFINITY).map(|hit| hit.t);
+        assert_eq!(linear_t, bvh_t, "bvh disagrees with the linear scan for {}", ray.direction());
+    }
+
+    println!("linear: {} rays, {} hits, {:?}", RAYS, linear_hits, linear_time);
+    println!("bvh:    {} rays, {} hits, {:?}", RAYS, bvh_hits, bvh_time);
+    println!(
+        "speedup: {:.1}x",
+        linear_time.as_secs_f64() / bvh_time.as_secs_f64().max(f64::MIN_POSITIVE)
+    );
+}
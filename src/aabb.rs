@@ -0,0 +1,132 @@
+use crate::ray3::Ray3;
+use crate::vec3::{Point3, Vec3};
+
+// axis aligned bounding box. everything that can be hit hands one of these out so the bvh
+// can skip whole chunks of the scene with a couple of slab tests
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    min: Point3,
+    max: Point3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    // corners can come in any order, we sort them out per axis
+    pub fn new(a: Point3, b: Point3) -> Self {
+        Self {
+            min: Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z())),
+        }
+    }
+
+    // inside out box, unioning anything into it gives back the other thing
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.min
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.max
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self {
+            min: Point3::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Point3::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    pub fn grow(&self, point: &Point3) -> Aabb {
+        self.union(&Aabb { min: *point, max: *point })
+    }
+
+    // widens any axis thinner than `minimum` around its middle, so flat things like an axis
+    // aligned triangle still have some volume for the slab test to find
+    pub fn padded(&self, minimum: f64) -> Aabb {
+        let mut min = self.min;
+        let mut max = self.max;
+        for axis in 0..3 {
+            if max[axis] - min[axis] < minimum {
+                let middle = 0.5 * (min[axis] + max[axis]);
+                min[axis] = middle - 0.5 * minimum;
+                max[axis] = middle + 0.5 * minimum;
+            }
+        }
+        Aabb { min, max }
+    }
+
+    // false for things like planes that go on forever, and for the empty box
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|axis| self.min[axis].is_finite() && self.max[axis].is_finite())
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.extent();
+        if d.x() < 0.0 || d.y() < 0.0 || d.z() < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let d = self.extent();
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    // slab test. inverse_direction is 1 / ray direction, worked out once per ray by the
+    // caller since the same ray gets tested against a lot of boxes
+    pub fn hit(&self, ray: &Ray3, inverse_direction: &Vec3, t_min: f64, t_max: f64) -> bool {
+        let origin = ray.origin();
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let t1 = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+            let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            // written this way round so a nan from 0 * inf doesn't shrink the interval
+            if t_near > t_min {
+                t_min = t_near;
+            }
+            if t_far < t_max {
+                t_max = t_far;
+            }
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
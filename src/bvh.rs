@@ -0,0 +1,223 @@
+use crate::aabb::Aabb;
+use crate::ray3::Ray3;
+use crate::vec3::{Point3, Vec3};
+
+// how many buckets the centroids get dropped into when looking for a split
+const SAH_BINS: usize = 16;
+// relative cost of stepping into a node vs intersecting one primitive
+const TRAVERSAL_COST: f64 = 1.0;
+const MAX_LEAF_SIZE: usize = 4;
+// past this depth we stop trusting the heuristic and split down the middle, which keeps
+// the traversal stack bounded
+const MAX_SAH_DEPTH: usize = 64;
+const STACK_SIZE: usize = 128;
+
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    // for a leaf this is the first slot in `indices`, for an interior node it's the
+    // right child (the left child always sits right after its parent)
+    offset: usize,
+    // number of primitives, 0 means interior node
+    count: usize,
+    // split axis, used to walk the children front to back
+    axis: usize,
+}
+
+// bounding volume hierarchy over a list of boxes. the tree only stores indices, so whoever
+// built it (the scene) is the one that actually intersects the primitives
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    // builds the tree with a binned surface area heuristic
+    pub fn build(boxes: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * boxes.len()),
+            indices: (0..boxes.len()).collect(),
+        };
+        if boxes.is_empty() {
+            return bvh;
+        }
+        let centroids: Vec<Point3> = boxes.iter().map(|b| b.centroid()).collect();
+        bvh.build_node(boxes, &centroids, 0, boxes.len(), 0);
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(node) => node.bounds,
+            None => Aabb::empty(),
+        }
+    }
+
+    fn build_node(
+        &mut self,
+        boxes: &[Aabb],
+        centroids: &[Point3],
+        start: usize,
+        end: usize,
+        depth: usize,
+    ) -> usize {
+        let node_index = self.nodes.len();
+        let mut bounds = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+        for &i in &self.indices[start..end] {
+            bounds = bounds.union(&boxes[i]);
+            centroid_bounds = centroid_bounds.grow(&centroids[i]);
+        }
+        self.nodes.push(BvhNode { bounds, offset: start, count: end - start, axis: 0 });
+
+        let count = end - start;
+        if count <= 1 {
+            return node_index;
+        }
+
+        // find the cheapest bin boundary over all three axes
+        let mut best: Option<(usize, usize, f64)> = None;
+        let extent = centroid_bounds.extent();
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 || depth >= MAX_SAH_DEPTH {
+                continue;
+            }
+            let mut bin_counts = [0usize; SAH_BINS];
+            let mut bin_bounds = [Aabb::empty(); SAH_BINS];
+            for &i in &self.indices[start..end] {
+                let bin = self.bin_of(&centroid_bounds, &centroids[i], axis);
+                bin_counts[bin] += 1;
+                bin_bounds[bin] = bin_bounds[bin].union(&boxes[i]);
+            }
+
+            // sweep from the right so every split knows the area and count to its right
+            let mut right_area = [0.0; SAH_BINS];
+            let mut right_count = [0usize; SAH_BINS];
+            let mut acc_bounds = Aabb::empty();
+            let mut acc_count = 0;
+            for bin in (1..SAH_BINS).rev() {
+                acc_bounds = acc_bounds.union(&bin_bounds[bin]);
+                acc_count += bin_counts[bin];
+                right_area[bin] = acc_bounds.surface_area();
+                right_count[bin] = acc_count;
+            }
+
+            let mut acc_bounds = Aabb::empty();
+            let mut acc_count = 0;
+            for split in 1..SAH_BINS {
+                acc_bounds = acc_bounds.union(&bin_bounds[split - 1]);
+                acc_count += bin_counts[split - 1];
+                if acc_count == 0 || right_count[split] == 0 {
+                    continue;
+                }
+                let cost = acc_bounds.surface_area() * acc_count as f64
+                    + right_area[split] * right_count[split] as f64;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((axis, split, cost));
+                }
+            }
+        }
+
+        let parent_area = bounds.surface_area();
+        let mid = match best {
+            Some((axis, split, cost)) => {
+                let split_cost = TRAVERSAL_COST + cost / parent_area.max(f64::MIN_POSITIVE);
+                if split_cost >= count as f64 && count <= MAX_LEAF_SIZE {
+                    return node_index;
+                }
+                self.nodes[node_index].axis = axis;
+                self.partition(&centroid_bounds, centroids, start, end, axis, split)
+            }
+            None => {
+                // every centroid sits on the same point (no split is going to help) or we
+                // are too deep, either way just cut the range in half
+                if count <= MAX_LEAF_SIZE {
+                    return node_index;
+                }
+                let axis = centroid_bounds.longest_axis();
+                self.nodes[node_index].axis = axis;
+                let mid = start + count / 2;
+                self.indices[start..end].select_nth_unstable_by(count / 2, |&a, &b| {
+                    centroids[a][axis].total_cmp(&centroids[b][axis])
+                });
+                mid
+            }
+        };
+
+        let axis = self.nodes[node_index].axis;
+        self.build_node(boxes, centroids, start, mid, depth + 1);
+        let right = self.build_node(boxes, centroids, mid, end, depth + 1);
+        self.nodes[node_index] = BvhNode { bounds, offset: right, count: 0, axis };
+        node_index
+    }
+
+    fn bin_of(&self, centroid_bounds: &Aabb, centroid: &Point3, axis: usize) -> usize {
+        let min = centroid_bounds.min()[axis];
+        let extent = centroid_bounds.extent()[axis];
+        let bin = ((centroid[axis] - min) / extent * SAH_BINS as f64) as usize;
+        bin.min(SAH_BINS - 1)
+    }
+
+    fn partition(
+        &mut self,
+        centroid_bounds: &Aabb,
+        centroids: &[Point3],
+        start: usize,
+        end: usize,
+        axis: usize,
+        split: usize,
+    ) -> usize {
+        let mut mid = start;
+        for i in start..end {
+            let index = self.indices[i];
+            if self.bin_of(centroid_bounds, &centroids[index], axis) < split {
+                self.indices.swap(i, mid);
+                mid += 1;
+            }
+        }
+        mid
+    }
+
+    // walks the tree front to back. `hit_primitive` gets a primitive index and the closest
+    // t found so far, and returns the new closest t if it got hit
+    pub fn traverse<F>(&self, ray: &Ray3, t_min: f64, t_max: f64, mut hit_primitive: F)
+    where
+        F: FnMut(usize, f64) -> Option<f64>,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let direction = ray.direction();
+        let inverse_direction = Vec3::new(1.0 / direction.x(), 1.0 / direction.y(), 1.0 / direction.z());
+        let mut closest = t_max;
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_size = 1;
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node = &self.nodes[stack[stack_size]];
+            if !node.bounds.hit(ray, &inverse_direction, t_min, closest) {
+                continue;
+            }
+            if node.count > 0 {
+                for &index in &self.indices[node.offset..node.offset + node.count] {
+                    if let Some(t) = hit_primitive(index, closest) {
+                        closest = t;
+                    }
+                }
+                continue;
+            }
+            let left = stack[stack_size] + 1;
+            let right = node.offset;
+            // push the far child first so the near one gets popped next
+            if direction[node.axis] < 0.0 {
+                stack[stack_size] = left;
+                stack[stack_size + 1] = right;
+            } else {
+                stack[stack_size] = right;
+                stack[stack_size + 1] = left;
+            }
+            stack_size += 2;
+        }
+    }
+}
@@ -1,42 +1,71 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::object::HitRecord;
 use crate::ray3::Ray3;
-use crate::object::*;
+use crate::scene::Scene;
 use crate::vec3::{Point3, Vec3, Color3};
 use crate::rand::Rand;
 
-pub fn make_spheres() -> Vec<Box<dyn HittableMaterial>> {
-    let mut objects: Vec<Box<dyn HittableMaterial>> = vec![
-        // Ground sphere (yellow Lambertian)
-        Box::new(LambertianSphere::new(
-            Point3::new(0.0, -100.5, -1.0),
-            95.0,
-            Color3::new(0.8, 0.8, 0.0),
-        )),
-    ];
-
-    for j in -10..10 as i32 {
-        for i in -10..10 as i32 {
-            objects.push(
-                Box::new(LambertianSphere::new(
-                    Point3::new(j as f64, -4.0, i as f64),
-                    0.5,
-                    Color3::new((j + 10) as f64 / 20.0, (i +10) as f64 / 20.0, 1.0),
-                ))
-            );
-        }
+// side length in pixels of the square tiles handed out to the render threads
+const TILE_SIZE: u32 = 16;
+// bounces that always happen before russian roulette is allowed to end a path
+const RUSSIAN_ROULETTE_DEPTH: u32 = 3;
+
+// multiple importance sampling weight for a sample drawn with density `pdf` when
+// `other_pdf` could have produced it too (veach's power heuristic, beta = 2)
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    if a + b <= 0.0 {
+        return 0.0;
     }
-    return objects;
+    a / (a + b)
 }
 
+// a rectangle of the image, handed to whichever render thread asks next
+struct Tile {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
 
 pub struct Camera {
-    aspect_ratio: f64,
     image_width: u32,
     image_height: u32,
-    focal_length: f64,
-    viewport_height: f64,
-    viewport_width: f64,
+    // vertical field of view in degrees
+    vfov: f64,
     camera_center: Point3,
-    samples_per_pixel: u8,
+    look_at: Point3,
+    vup: Vec3,
+    // angle in degrees of the cone from the lens disk to a pixel on the focus plane, 0 is
+    // a pinhole camera
+    defocus_angle: f64,
+    // distance to the plane that's in perfect focus, None focuses on look_at
+    focus_distance: Option<f64>,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    // 0 means use every core we can find
+    threads: usize,
+    seed: u64,
+    // aim rays at lights on top of scattering (next event estimation), on by default.
+    // turning it off leaves plain path tracing, mostly there to compare against
+    light_sampling: bool,
+    // print a tiles done count to stderr while rendering, off by default so library users
+    // and tests stay quiet
+    progress: bool,
+
+    focal_length: f64,
+    // camera frame: u points right, v points up, w points backwards (away from look_at)
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    // the lens disk, spanned by these two radius vectors
+    defocus_disk_u: Vec3,
+    defocus_disk_v: Vec3,
 
     // vector across the horizontal of the viewport
     viewport_u: Vec3,
@@ -49,134 +78,346 @@ pub struct Camera {
 }
 
 impl Camera {
+    // camera sitting at look_from pointed at look_at. vup is roughly which way is up, it
+    // just has to not be parallel to the viewing direction. vfov is in degrees
     pub fn new(
         aspect_ratio: f64,
         image_width: u32,
-        focal_length: f64,
-        viewport_height: f64,
-        camera_center: Point3,
+        look_from: Point3,
+        look_at: Point3,
+        vup: Vec3,
+        vfov: f64,
     ) -> Self {
         let mut image_height: u32 = (image_width as f64 / aspect_ratio) as u32;
         if image_height < 1 {
             image_height = 1;
         }
 
-        let viewport_width: f64 = viewport_height * (image_width as f64 / image_height as f64);
+        let mut camera = Self {
+            image_width,
+            image_height,
+            vfov,
+            camera_center: look_from,
+            look_at,
+            vup,
+            defocus_angle: 0.0,
+            focus_distance: None,
+            samples_per_pixel: 2,
+            max_depth: 50,
+            threads: 0,
+            seed: 0,
+            light_sampling: true,
+            progress: false,
+            focal_length: 1.0,
+            u: Vec3::default(),
+            v: Vec3::default(),
+            w: Vec3::default(),
+            defocus_disk_u: Vec3::default(),
+            defocus_disk_v: Vec3::default(),
+            viewport_u: Vec3::default(),
+            viewport_v: Vec3::default(),
+            pixel_x_delta: Vec3::default(),
+            pixel_y_delta: Vec3::default(),
+        };
+        camera.initialize();
+        camera
+    }
+
+    // works out the camera frame, viewport and pixel deltas, has to run again whenever the
+    // view or the image size changes
+    fn initialize(&mut self) {
+        // the viewport sits on the focus plane, so everything there lands on the same pixel
+        // no matter where on the lens the ray starts
+        self.focal_length = self
+            .focus_distance
+            .unwrap_or_else(|| (self.camera_center - self.look_at).length());
+        let h = (self.vfov.to_radians() / 2.0).tan();
+        let viewport_height = 2.0 * h * self.focal_length;
+        let viewport_width: f64 = viewport_height * (self.image_width as f64 / self.image_height as f64);
 
-        // because in our camera frame, the x is right and the y is up and z is in the viewing
-        // direction
+        self.w = (self.camera_center - self.look_at).unit_vector();
+        self.u = self.vup.cross(&self.w).unit_vector();
+        self.v = self.w.cross(&self.u);
+
+        // because in our camera frame, the x is right and the y is up
         // but in our image frame, x is right y is down
         // so need to inverse the y
-        let viewport_u = Vec3::new(viewport_width, 0.0, 0.0);
-        let viewport_v = Vec3::new(0.0, -viewport_height, 0.0);
+        self.viewport_u = viewport_width * self.u;
+        self.viewport_v = viewport_height * -self.v;
 
-        let pixel_x_delta = viewport_u / image_width as f64;
-        let pixel_y_delta = viewport_v / image_height as f64;
+        self.pixel_x_delta = self.viewport_u / self.image_width as f64;
+        self.pixel_y_delta = self.viewport_v / self.image_height as f64;
 
-        Self {
-            aspect_ratio,
-            image_width,
-            image_height,
-            focal_length,
-            viewport_height,
-            viewport_width,
-            camera_center,
-            samples_per_pixel: 2, 
-            viewport_u,
-            viewport_v,
-            pixel_x_delta,
-            pixel_y_delta
+        let defocus_radius = self.focal_length * (self.defocus_angle.to_radians() / 2.0).tan();
+        self.defocus_disk_u = defocus_radius * self.u;
+        self.defocus_disk_v = defocus_radius * self.v;
+    }
+
+    // thin lens depth of field. defocus_angle is in degrees, bigger means blurrier away
+    // from focus_distance. an angle of 0 turns it back into a pinhole camera
+    pub fn with_defocus(mut self, defocus_angle: f64, focus_distance: f64) -> Self {
+        self.defocus_angle = defocus_angle.max(0.0);
+        self.focus_distance = Some(focus_distance);
+        self.initialize();
+        self
+    }
+
+    // both at least 1
+    pub fn with_image_size(mut self, image_width: u32, image_height: u32) -> Self {
+        self.image_width = image_width.max(1);
+        self.image_height = image_height.max(1);
+        self.initialize();
+        self
+    }
+
+    pub fn with_samples_per_pixel(mut self, samples_per_pixel: u32) -> Self {
+        self.samples_per_pixel = samples_per_pixel.max(1);
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_light_sampling(mut self, light_sampling: bool) -> Self {
+        self.light_sampling = light_sampling;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        if self.threads > 0 {
+            return self.threads;
+        }
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn light_sampling(&self) -> bool {
+        self.light_sampling
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.image_width as f64 / self.image_height as f64
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        let mut tiles = Vec::new();
+        for y0 in (0..self.image_height).step_by(TILE_SIZE as usize) {
+            for x0 in (0..self.image_width).step_by(TILE_SIZE as usize) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + TILE_SIZE).min(self.image_width),
+                    y1: (y0 + TILE_SIZE).min(self.image_height),
+                });
+            }
         }
+        tiles
     }
 
+    // renders the image in tiles spread over `threads()` threads. each thread grabs the
+    // next tile off a shared counter until there aren't any left
     pub fn smile(
-        &self
+        &self,
+        scene: &Scene,
     ) -> Vec<Vec<Vec3>> {
-        let mut randomizer = Rand::new_with_nanos();
         let image_width: usize = self.image_width() as usize;
         let image_height: usize = self.image_height() as usize;
-        let mut image = vec![vec![Vec3::new(0.0, 0.0, 0.0); image_width]; image_height];
-        for j in 0..self.image_height as usize {
-            println!("{:#?}", j);
-            for i in 0..image_width as usize {
+        let image = Mutex::new(vec![vec![Vec3::new(0.0, 0.0, 0.0); image_width]; image_height]);
+        let tiles = self.tiles();
+        let next_tile = AtomicUsize::new(0);
+        let tiles_done = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..self.threads().min(tiles.len()) {
+                s.spawn(|| {
+                    loop {
+                        let tile_index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let Some(tile) = tiles.get(tile_index) else {
+                            break;
+                        };
+                        let pixels = self.render_tile(scene, tile);
+
+                        let mut image = image.lock().unwrap();
+                        for (row, y) in pixels.chunks((tile.x1 - tile.x0) as usize).zip(tile.y0..tile.y1) {
+                            image[y as usize][tile.x0 as usize..tile.x1 as usize].copy_from_slice(row);
+                        }
+                        drop(image);
+
+                        let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                        if self.progress {
+                            eprint!("\rtiles: {}/{}", done, tiles.len());
+                            let _ = io::stderr().flush();
+                        }
+                    }
+                });
+            }
+        });
+        if self.progress {
+            eprintln!();
+        }
+        image.into_inner().unwrap()
+    }
+
+    // renders one tile, returns its pixels row by row
+    fn render_tile(&self, scene: &Scene, tile: &Tile) -> Vec<Vec3> {
+        let mut pixels = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+        for j in tile.y0..tile.y1 {
+            for i in tile.x0..tile.x1 {
+                // every pixel gets its own stream, so a seed renders the same image no
+                // matter how many threads or what tile size we use
+                let pixel_index = j as u64 * self.image_width as u64 + i as u64;
+                let mut randomizer = Rand::new_with_stream(self.seed, pixel_index);
                 let mut pixel_color = Vec3::new(0.0, 0.0, 0.0); // this is rgb. can be changed for
                                                                  // tint. lower value = reduce that
                                                                  // specific thing (ie anti-red
                                                                  // tint) higher = more ( ie red or
                                                                  // green tint). all lower =
                                                                  // darker, all higher = lighter
-                for sample in 0..self.samples_per_pixel as usize {
-                    let offset_i = randomizer.next_with_range(-0.5, 0.5) as f64;
-                    let offset_j = randomizer.next_with_range(-0.5, 0.5) as f64;
-
-                    let pixel_center = self.pixel_00_location()
-                        + ((i as f64 + offset_i) * self.pixel_x_delta())
-                        + ((j as f64 + offset_j) * self.pixel_y_delta());
-                        
-                    let ray_direction = Ray3::new(
-                        self.camera_center(),
-                        pixel_center - self.camera_center(),
-                    );
-                    
-                    pixel_color = pixel_color + self.ray_color(&ray_direction, 50, &mut randomizer);
+                for _ in 0..self.samples_per_pixel {
+                    let ray_direction = self.get_ray(i, j, &mut randomizer);
+                    pixel_color += self.ray_color(&ray_direction, scene, self.max_depth, &mut randomizer);
                 }
-                image[j][i] = pixel_color / (self.samples_per_pixel) as f64
+                pixels.push(pixel_color / (self.samples_per_pixel) as f64);
             }
         }
-        return image;
+        pixels
+    }
+
+    // a ray through a random spot in pixel (i, j), starting from a random spot on the lens
+    pub fn get_ray(&self, i: u32, j: u32, randomizer: &mut Rand) -> Ray3 {
+        let offset_i = randomizer.next_with_range(-0.5, 0.5);
+        let offset_j = randomizer.next_with_range(-0.5, 0.5);
+
+        let pixel_center = self.pixel_00_location()
+            + ((i as f64 + offset_i) * self.pixel_x_delta())
+            + ((j as f64 + offset_j) * self.pixel_y_delta());
+
+        let origin = if self.defocus_angle <= 0.0 {
+            self.camera_center()
+        } else {
+            let p = Vec3::random_in_unit_disk(randomizer);
+            self.camera_center() + p.x() * self.defocus_disk_u + p.y() * self.defocus_disk_v
+        };
+        Ray3::new(origin, pixel_center - origin)
     }
 
     pub fn ray_color(
         &self,
         ray: &Ray3,
-        max_depth: i32,
+        scene: &Scene,
+        max_depth: u32,
         randomizer: &mut Rand
     ) -> Vec3 {
-        if max_depth <= 0 {
-            return Color3::new(0.0, 0.0, 0.0);
-        }
-        // let objects:  = vec![
-        //     LambertianSphere::new(Point3::new(0.0, 0.0, -1.0), 0.5),
-        //     MetalSphere::new(Point3::new(0.0, -100.5, -1.0), 100.0),
-        //     // Sphere::new(Point3::new(-1.0, -1.0, -1.3), 0.6)
-        // ];
-        let objects = make_spheres();
-        let mut t = -1.0;
-        let mut hit_i = 0;
-        for i in 0..objects.len() {
-            let t_temp = objects[i].hit_it(ray);
-            if (t == -1.0 && t_temp > 0.000001) || (t_temp < t && t_temp > 0.000001) {
-                t = t_temp;
-                hit_i = i;
+        // how much of whatever light we find along the way still makes it back to the camera
+        let mut throughput = Color3::new(1.0, 1.0, 1.0);
+        let mut radiance = Color3::new(0.0, 0.0, 0.0);
+        let mut ray = *ray;
+        // density the last bounce picked the current direction with. None for camera rays
+        // and after specular bounces, where hitting a light is the only way to see it
+        let mut scatter_pdf: Option<f64> = None;
+        for depth in 0..max_depth {
+            // the light sampling at the last bounce could have found whatever light we run
+            // into here too, so it only gets its share of the two
+            let weight = |scatter_pdf: Option<f64>| match scatter_pdf {
+                Some(pdf) => power_heuristic(pdf, scene.light_pdf(&ray.origin(), &ray.direction())),
+                None => 1.0,
+            };
+            // start a little off the surface so a bounce doesn't hit the thing it left from
+            let Some(hit) = scene.hit(&ray, 0.001, f64::INFINITY) else {
+                return radiance + throughput * scene.environment().radiance(&ray.direction()) * weight(scatter_pdf);
+            };
+            if hit.material.is_emissive() {
+                radiance += throughput * hit.material.emitted(&ray, &hit) * weight(scatter_pdf);
+            }
+
+            // next event estimation: aim a ray straight at a light and see if it gets there
+            let sample_lights = self.light_sampling && !hit.material.is_specular();
+            if sample_lights {
+                radiance += throughput * self.sample_direct(&ray, &hit, scene, randomizer);
+            }
+
+            let Some((attenuation, ray_scattered)) = hit.material.scatter(&ray, &hit, randomizer) else {
+                return radiance;
+            };
+            scatter_pdf = if sample_lights {
+                Some(hit.material.pdf(&ray, &hit, &ray_scattered.direction()))
+            } else {
+                None
+            };
+            throughput = throughput * attenuation;
+            ray = ray_scattered;
+
+            // russian roulette: paths that can't carry much light anymore get killed off
+            // at random, and the survivors are scaled up by the same odds so on average
+            // nothing is lost
+            if depth + 1 >= RUSSIAN_ROULETTE_DEPTH {
+                let survival = throughput.x().max(throughput.y()).max(throughput.z()).min(1.0);
+                if randomizer.next_f64() >= survival {
+                    return radiance;
+                }
+                throughput = throughput / survival;
             }
         }
-        if t > 0.0 {
-            let mut ray_scattered = Ray3::empty_new();
-            let attenuation = objects[hit_i].scatter(ray, t, &mut ray_scattered, randomizer);
-            return attenuation * self.ray_color(&ray_scattered, max_depth - 1, randomizer);
-            // if objects[hit_i].scatter(
-            // let mut N = (ray.at(t) - objects[hit_i].center).unit_vector();
-            // for diffusion coloring (image 7 on the guide)
-            // if N.dot(&ray.direction()) > 0.0 { // so if they are in the same direction
-            //     N = Vec3::new(0.0, 0.0, 0.0) - N; // make em be in different directions
-            // }
-            // let direction_vector = N + Vec3::random_unit_vector(randomizer);
-            // let direction_vector = Vec3::random_on_hemisphere(&N, randomizer);
-            // return 0.5 * self.ray_color(&Ray3::new(ray.at(t), direction_vector), max_depth - 1, randomizer);
-            // for normal coloring
-            // if N.dot(&ray.direction()) > 0.0 {
-            //     N = Vec3::new(0.0, 0.0, 0.0) - N;
-            // }
-            // return 0.5 * Vec3::new(N.x()+1.0, N.y()+1.0, N.z()+1.0);
-        }
+        radiance
+    }
 
-        let direction = ray.direction();
-        let a = 0.5 * (direction.y() + 1.0);
-        (1.0 - a) * Vec3::new(1.0, 1.0, 1.0) + a * Vec3::new(0.5, 0.7, 1.0)
+    // light reaching the hit straight from a light source, weighted against finding the
+    // same light by scattering
+    fn sample_direct(&self, ray: &Ray3, hit: &HitRecord, scene: &Scene, randomizer: &mut Rand) -> Color3 {
+        let black = Color3::new(0.0, 0.0, 0.0);
+        let Some(direction) = scene.sample_light(&hit.point, randomizer) else {
+            return black;
+        };
+        let light_pdf = scene.light_pdf(&hit.point, &direction);
+        if light_pdf <= 0.0 {
+            return black;
+        }
+        let brdf = hit.material.eval(ray, hit, &direction);
+        if brdf.near_zero() {
+            return black;
+        }
+        // whatever is seen first is what counts, a light in front of the one we aimed for
+        // (or the environment behind it) is just as good since light_pdf covers all of them
+        let shadow_ray = Ray3::new(hit.point, direction);
+        let light = match scene.hit(&shadow_ray, 0.001, f64::INFINITY) {
+            Some(light_hit) if light_hit.material.is_emissive() => light_hit.material.emitted(&shadow_ray, &light_hit),
+            Some(_) => return black,
+            None => scene.environment().radiance(&shadow_ray.direction()),
+        };
+        let weight = power_heuristic(light_pdf, hit.material.pdf(ray, hit, &direction));
+        brdf * light * (weight / light_pdf)
     }
 
     pub fn viewport_u_l(&self) -> Vec3 {
-        self.camera_center - Vec3::new(0.0, 0.0, self.focal_length) - self.viewport_u/2.0 - self.viewport_v/2.0
+        self.camera_center - self.focal_length * self.w - self.viewport_u/2.0 - self.viewport_v/2.0
     }
 
     pub fn pixel_00_location(&self) -> Point3 {
@@ -186,6 +427,26 @@ impl Camera {
     pub fn camera_center(&self) -> Vec3 {
         self.camera_center
     }
+
+    pub fn look_at(&self) -> Point3 {
+        self.look_at
+    }
+
+    pub fn vup(&self) -> Vec3 {
+        self.vup
+    }
+
+    pub fn vfov(&self) -> f64 {
+        self.vfov
+    }
+
+    pub fn defocus_angle(&self) -> f64 {
+        self.defocus_angle
+    }
+
+    pub fn focus_distance(&self) -> f64 {
+        self.focal_length
+    }
      
     pub fn image_height(&self) -> u32 {
         self.image_height
@@ -203,3 +464,146 @@ impl Camera {
         self.pixel_y_delta
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::material::{DiffuseLight, Lambertian, Material};
+    use crate::object::{Hittable, Sphere};
+
+    // where a ray crosses the plane `distance` in front of the camera, square to the view
+    fn crossing(ray: &Ray3, center: &Point3, forward: &Vec3, distance: f64) -> Point3 {
+        let t = (distance - (ray.origin() - *center).dot(forward)) / ray.direction().dot(forward);
+        ray.at(t)
+    }
+
+    // furthest any of the points is from their average
+    fn spread(points: &[Point3]) -> f64 {
+        let mean = points.iter().fold(Vec3::new(0.0, 0.0, 0.0), |sum, p| sum + *p) / points.len() as f64;
+        points.iter().map(|p| (*p - mean).length()).fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn focus_plane_stays_sharp() {
+        let look_from = Point3::new(1.0, 2.0, 3.0);
+        let look_at = Point3::new(-2.0, 0.5, -4.0);
+        let forward = (look_at - look_from).unit_vector();
+        let focus_distance = 6.0;
+        // a wide open lens, so anything off the focus plane smears a long way
+        let camera = Camera::new(1.5, 1200, look_from, look_at, Vec3::new(0.0, 1.0, 0.0), 40.0)
+            .with_defocus(20.0, focus_distance);
+        let pixel = camera.pixel_x_delta().length().max(camera.pixel_y_delta().length());
+        let mut randomizer = Rand::new_with_seed(1);
+
+        for (i, j) in [(600, 400), (0, 0), (1199, 799), (250, 700)] {
+            let rays: Vec<Ray3> = (0..2000).map(|_| camera.get_ray(i, j, &mut randomizer)).collect();
+            let origins: Vec<Point3> = rays.iter().map(|ray| ray.origin()).collect();
+            let lens_radius = focus_distance * 10f64.to_radians().tan();
+            assert!(spread(&origins) > 0.9 * lens_radius, "the rays should start all over the lens");
+
+            // the only spread left on the focus plane is the jitter inside the pixel
+            let focused: Vec<Point3> = rays.iter().map(|ray| crossing(ray, &look_from, &forward, focus_distance)).collect();
+            assert!(spread(&focused) <= pixel, "pixel ({}, {}) is blurred on the focus plane", i, j);
+
+            for distance in [0.5 * focus_distance, 2.0 * focus_distance] {
+                let points: Vec<Point3> = rays.iter().map(|ray| crossing(ray, &look_from, &forward, distance)).collect();
+                assert!(spread(&points) > 50.0 * pixel, "pixel ({}, {}) is still sharp at {}", i, j, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn no_defocus_is_a_pinhole() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let camera = Camera::new(1.0, 100, origin, Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 90.0)
+            .with_defocus(0.0, 3.0);
+        let mut randomizer = Rand::new_with_seed(2);
+        for _ in 0..100 {
+            assert_eq!(camera.get_ray(10, 20, &mut randomizer).origin().length(), 0.0);
+        }
+    }
+
+    // a small room lit by a small sphere light, walls on the insides of huge spheres so
+    // nothing from the sky gets in
+    fn small_room() -> Scene {
+        let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.73, 0.73, 0.73)));
+        let red: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.65, 0.05, 0.05)));
+        let green: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.12, 0.45, 0.15)));
+        let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color3::new(50.0, 50.0, 50.0)));
+        let wall = |center: Point3, material: &Arc<dyn Material>| -> Box<dyn Hittable> {
+            Box::new(Sphere::new(center, 1000.0, material.clone()))
+        };
+        Scene::new(vec![
+            wall(Point3::new(0.0, -1002.0, 0.0), &white),
+            wall(Point3::new(0.0, 1002.0, 0.0), &white),
+            wall(Point3::new(0.0, 0.0, -1002.0), &white),
+            wall(Point3::new(0.0, 0.0, 1004.0), &white),
+            wall(Point3::new(-1002.0, 0.0, 0.0), &red),
+            wall(Point3::new(1002.0, 0.0, 0.0), &green),
+            Box::new(Sphere::new(Point3::new(-0.7, -1.4, -0.5), 0.6, white.clone())),
+            Box::new(Sphere::new(Point3::new(0.0, 1.7, 0.0), 0.2, light)),
+        ])
+    }
+
+    fn room_camera(samples: u32, seed: u64, light_sampling: bool) -> Camera {
+        Camera::new(
+            1.0,
+            24,
+            Point3::new(0.0, 0.0, 3.5),
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            60.0,
+        )
+        .with_samples_per_pixel(samples)
+        .with_seed(seed)
+        .with_light_sampling(light_sampling)
+    }
+
+    fn render_room(scene: &Scene, samples: u32, seed: u64, light_sampling: bool) -> Vec<Color3> {
+        room_camera(samples, seed, light_sampling).smile(scene).into_iter().flatten().collect()
+    }
+
+    // the image is 24 wide so it's split into 4 tiles, which the threads grab in whatever
+    // order they get to them
+    #[test]
+    fn same_seed_same_image_on_any_thread_count() {
+        let scene = small_room();
+        let render = |seed: u64, threads: usize| -> Vec<[u64; 3]> {
+            let image = room_camera(4, seed, true).with_threads(threads).smile(&scene);
+            image.into_iter().flatten().map(|pixel| [pixel.x(), pixel.y(), pixel.z()].map(f64::to_bits)).collect()
+        };
+        let one = render(7, 1);
+        assert!(one == render(7, 4), "the image changed with the thread count");
+        assert!(one != render(8, 4), "a different seed should give a different image");
+    }
+
+    // light sampling should come out a lot less noisy than plain path tracing at the same
+    // sample count, and agree with it on the average brightness. noise is measured between two
+    // renders with different seeds, so there's no need for an expensive reference image
+    #[test]
+    fn light_sampling_lowers_noise() {
+        let scene = small_room();
+        let noise = |light_sampling: bool| {
+            let first = render_room(&scene, 16, 1, light_sampling);
+            let second = render_room(&scene, 16, 2, light_sampling);
+            let sum: f64 = first.iter().zip(&second).map(|(a, b)| (*a - *b).length_squared() / 3.0).sum();
+            (sum / (2.0 * first.len() as f64)).sqrt()
+        };
+        let (noise_with, noise_without) = (noise(true), noise(false));
+        assert!(
+            noise_with * 2.0 < noise_without,
+            "light sampling should at least halve the noise, got {} against {}",
+            noise_with,
+            noise_without
+        );
+
+        let mean = |image: Vec<Color3>| {
+            image.iter().map(|pixel| pixel.x() + pixel.y() + pixel.z()).sum::<f64>() / (3.0 * image.len() as f64)
+        };
+        let with_lights = mean(render_room(&scene, 64, 3, true));
+        let without_lights = mean(render_room(&scene, 256, 4, false));
+        let bias = (with_lights - without_lights).abs() / without_lights;
+        assert!(bias < 0.05, "light sampling is {:.1}% off plain path tracing", 100.0 * bias);
+    }
+}
@@ -0,0 +1,327 @@
+// deflate (rfc 1951) and the zlib wrapper around it (rfc 1950), compression only. png and
+// exr both want zlib streams and we're not pulling in a crate for it
+//
+// matches are found with a hash chain over a 32k window and everything goes out in a
+// single block with the fixed huffman codes. not as tight as zlib -9 but close enough for
+// rendered images, and a lot less code than building dynamic trees
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const WINDOW_MASK: usize = WINDOW_SIZE - 1;
+const HASH_BITS: u32 = 15;
+const HASH_SIZE: usize = 1 << HASH_BITS;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// how many earlier positions with the same hash get checked before we settle
+const MAX_CHAIN: usize = 128;
+// stop looking once a match is this long
+const GOOD_MATCH: usize = 128;
+
+// base length and extra bits for length symbols 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+// base distance and extra bits for distance symbols 0..=29
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// deflate packs bits starting from the least significant one
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), buffer: 0, bit_count: 0 }
+    }
+
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= (value as u64) << self.bit_count;
+        self.bit_count += count;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    // huffman codes go out most significant bit first, so flip them around
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+// symbol 0..=287 of the fixed literal/length code
+fn write_literal_length(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0b0011_0000 + symbol, 8),
+        144..=255 => writer.write_code(0b1_1001_0000 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0b1100_0000 + symbol - 280, 8),
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let length_index = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+    write_literal_length(writer, 257 + length_index as u32);
+    writer.write_bits(
+        (length - LENGTH_BASE[length_index] as usize) as u32,
+        LENGTH_EXTRA[length_index] as u32,
+    );
+
+    let distance_index = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+    writer.write_code(distance_index as u32, 5);
+    writer.write_bits(
+        (distance - DISTANCE_BASE[distance_index] as usize) as u32,
+        DISTANCE_EXTRA[distance_index] as u32,
+    );
+}
+
+fn hash(data: &[u8], position: usize) -> usize {
+    let value = (data[position] as u32) << 16 | (data[position + 1] as u32) << 8 | data[position + 2] as u32;
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+// raw deflate stream, no zlib header
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    // one final block with fixed codes
+    writer.write_bits(1, 1);
+    writer.write_bits(1, 2);
+
+    // head holds the latest position for every hash, prev chains back to older ones.
+    // positions are stored + 1 so 0 can mean empty
+    let mut head = vec![0usize; HASH_SIZE];
+    let mut prev = vec![0usize; WINDOW_SIZE];
+    let insert = |position: usize, head: &mut [usize], prev: &mut [usize]| {
+        if position + MIN_MATCH <= data.len() {
+            let h = hash(data, position);
+            prev[position & WINDOW_MASK] = head[h];
+            head[h] = position + 1;
+        }
+    };
+
+    let mut position = 0;
+    while position < data.len() {
+        let mut best_length = 0;
+        let mut best_distance = 0;
+        if position + MIN_MATCH <= data.len() {
+            let max_length = MAX_MATCH.min(data.len() - position);
+            let mut candidate = head[hash(data, position)];
+            let mut chain = 0;
+            while candidate > 0 && chain < MAX_CHAIN {
+                let start = candidate - 1;
+                let distance = position - start;
+                if distance > WINDOW_SIZE {
+                    break;
+                }
+                if data[start + best_length.min(max_length - 1)] == data[position + best_length.min(max_length - 1)] {
+                    let mut length = 0;
+                    while length < max_length && data[start + length] == data[position + length] {
+                        length += 1;
+                    }
+                    if length > best_length {
+                        best_length = length;
+                        best_distance = distance;
+                        if length >= GOOD_MATCH || length == max_length {
+                            break;
+                        }
+                    }
+                }
+                let next = prev[start & WINDOW_MASK];
+                // the slot may have been reused by a newer position, which would loop us
+                if next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            write_match(&mut writer, best_length, best_distance);
+            for p in position..position + best_length {
+                insert(p, &mut head, &mut prev);
+            }
+            position += best_length;
+        } else {
+            write_literal_length(&mut writer, data[position] as u32);
+            insert(position, &mut head, &mut prev);
+            position += 1;
+        }
+    }
+
+    write_literal_length(&mut writer, 256);
+    writer.finish()
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    // 5552 is the most bytes we can sum before b can overflow a u32
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+// deflate with the two byte zlib header in front and the adler32 checksum at the end
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // cm = 8 (deflate), cinfo = 7 (32k window), flevel = 2, and fcheck so the header is a
+    // multiple of 31
+    let mut out = vec![0x78, 0x9c];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // just enough inflate to read back what we write: stored and fixed huffman blocks. a
+    // dynamic block is a failure, the encoder never makes those
+    struct Bits<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl Bits<'_> {
+        fn bit(&mut self) -> u32 {
+            let byte = self.data[self.position / 8];
+            let bit = (byte >> (self.position % 8)) & 1;
+            self.position += 1;
+            bit as u32
+        }
+
+        // extra bits and header fields, least significant first
+        fn bits(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |value, i| value | (self.bit() << i))
+        }
+
+        // huffman codes, most significant first
+        fn code(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |value, _| (value << 1) | self.bit())
+        }
+
+        fn fixed_literal_length(&mut self) -> u32 {
+            let code = self.code(7);
+            if code <= 0b0010111 {
+                return 256 + code;
+            }
+            let code = (code << 1) | self.bit();
+            match code {
+                0x30..=0xbf => code - 0x30,
+                0xc0..=0xc7 => 280 + code - 0xc0,
+                _ => 144 + ((code << 1) | self.bit()) - 0x190,
+            }
+        }
+    }
+
+    const LENGTH_BASE: [usize; 29] =
+        [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+    const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+    const DISTANCE_BASE: [usize; 30] = [
+        1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+        8193, 12289, 16385, 24577,
+    ];
+    const DISTANCE_EXTRA: [u32; 30] =
+        [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+    // raw deflate, gives back the data and how many bytes of input it used
+    pub(crate) fn inflate(data: &[u8]) -> (Vec<u8>, usize) {
+        let mut bits = Bits { data, position: 0 };
+        let mut out = Vec::new();
+        loop {
+            let last = bits.bits(1) == 1;
+            match bits.bits(2) {
+                0 => {
+                    bits.position = bits.position.div_ceil(8) * 8;
+                    let length = bits.bits(16) as usize;
+                    assert_eq!(bits.bits(16) as usize, !length & 0xffff, "stored block length check");
+                    let start = bits.position / 8;
+                    out.extend_from_slice(&data[start..start + length]);
+                    bits.position += 8 * length;
+                }
+                1 => loop {
+                    let symbol = bits.fixed_literal_length() as usize;
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+                    let length = LENGTH_BASE[symbol - 257] + bits.bits(LENGTH_EXTRA[symbol - 257]) as usize;
+                    let code = bits.code(5) as usize;
+                    let distance = DISTANCE_BASE[code] + bits.bits(DISTANCE_EXTRA[code]) as usize;
+                    assert!(distance <= out.len(), "distance {} reaches back before the start", distance);
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                },
+                kind => panic!("unexpected block type {}", kind),
+            }
+            if last {
+                return (out, bits.position.div_ceil(8));
+            }
+        }
+    }
+
+    // checks the zlib header and the adler32 at the end
+    pub(crate) fn zlib_decompress(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data[0] & 0x0f, 8, "zlib method has to be deflate");
+        assert_eq!((data[0] as u32 * 256 + data[1] as u32) % 31, 0, "zlib header check");
+        let (out, used) = inflate(&data[2..]);
+        assert_eq!(2 + used + 4, data.len(), "junk after the deflate stream");
+        let checksum = u32::from_be_bytes([data[2 + used], data[3 + used], data[4 + used], data[5 + used]]);
+        assert_eq!(checksum, adler32(&out), "adler32 mismatch");
+        out
+    }
+
+    #[test]
+    fn inflate_reads_stored_blocks() {
+        // a stored block that isn't last, then a fixed one with just the end of block code
+        let mut stream = vec![0b000, 5, 0, !5, !0];
+        stream.extend_from_slice(b"hello");
+        stream.extend_from_slice(&[0b011, 0]);
+        assert_eq!(inflate(&stream).0, b"hello");
+    }
+
+    // long runs, repeats further back than one match can reach and bytes that never repeat
+    #[test]
+    fn round_trips() {
+        let mut data = vec![7u8; 1000];
+        data.extend((0..70_000u64).map(|i| (i * i % 251) as u8));
+        let head = data[..40_000].to_vec();
+        data.extend(head);
+        for input in [&[][..], b"a", b"abcabcabcabc", &data] {
+            assert_eq!(zlib_decompress(&zlib_compress(input)), input);
+        }
+    }
+}
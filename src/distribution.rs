@@ -0,0 +1,112 @@
+// piecewise constant distributions for importance sampling, one dimensional and two
+// dimensional (a 1d distribution for every row plus one over the rows). the 2d one is what
+// lets an environment map pick its bright pixels more often than its dark ones
+// https://pbr-book.org/3ed-2018/Monte_Carlo_Integration/Sampling_Random_Variables
+
+pub struct Distribution1D {
+    function: Vec<f64>,
+    // function.len() + 1 entries, from 0 up to 1
+    cdf: Vec<f64>,
+    // integral of the function over [0, 1]
+    integral: f64,
+}
+
+impl Distribution1D {
+    // function values have to be >= 0, one per equally sized bucket of [0, 1]
+    pub fn new(function: Vec<f64>) -> Self {
+        let n = function.len();
+        assert!(n > 0, "distribution needs at least one value");
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            cdf[i] = cdf[i - 1] + function[i - 1] / n as f64;
+        }
+        let integral = cdf[n];
+        if integral > 0.0 {
+            for value in cdf.iter_mut() {
+                *value /= integral;
+            }
+        } else {
+            // nothing to go on, fall back to uniform
+            for (i, value) in cdf.iter_mut().enumerate() {
+                *value = i as f64 / n as f64;
+            }
+        }
+        Self { function, cdf, integral }
+    }
+
+    pub fn len(&self) -> usize {
+        self.function.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.function.is_empty()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // maps a uniform u in [0, 1) to x in [0, 1). gives back x, its density and the bucket
+    // it fell in
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.len();
+        let offset = (self.cdf.partition_point(|&value| value <= u).max(1) - 1).min(n - 1);
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+        let pdf = if self.integral > 0.0 { self.function[offset] / self.integral } else { 1.0 };
+        (((offset as f64 + du) / n as f64).min(1.0 - f64::EPSILON), pdf, offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        if self.integral <= 0.0 {
+            return 1.0;
+        }
+        self.function[self.bucket(x)] / self.integral
+    }
+
+    fn bucket(&self, x: f64) -> usize {
+        ((x * self.len() as f64).max(0.0) as usize).min(self.len() - 1)
+    }
+}
+
+pub struct Distribution2D {
+    // one per row, over u
+    conditional: Vec<Distribution1D>,
+    // over the rows, v
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // values are row by row, `width` of them per row
+    pub fn new(function: &[f64], width: usize) -> Self {
+        assert!(width > 0 && !function.is_empty() && function.len().is_multiple_of(width), "distribution has to be a non-empty rectangle");
+        let conditional: Vec<Distribution1D> = function
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|row| row.integral()).collect());
+        Self { conditional, marginal }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.marginal.integral()
+    }
+
+    // two uniform numbers to (u, v) in [0, 1)^2 and the density of picking it
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        if self.marginal.integral() <= 0.0 {
+            return 1.0;
+        }
+        let row = &self.conditional[self.marginal.bucket(v)];
+        row.function[row.bucket(u)] / self.marginal.integral()
+    }
+}
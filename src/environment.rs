@@ -0,0 +1,244 @@
+use std::f64::consts::PI;
+
+use crate::distribution::Distribution2D;
+use crate::rand::Rand;
+use crate::tonemap::luminance;
+use crate::vec3::{Color3, Vec3};
+
+// what a ray sees when it leaves the scene without hitting anything. the scene owns one and
+// the camera asks it for the light coming in from whichever direction the ray escaped in
+pub trait Environment: Send + Sync {
+    // direction is unit length
+    fn radiance(&self, direction: &Vec3) -> Color3;
+
+    // same deal as for shapes: environments that can sample themselves get picked along
+    // with the lights, the others are only found by rays that miss everything
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // solid angle density of `random` picking `direction`
+    fn pdf_value(&self, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    fn random(&self, _randomizer: &mut Rand) -> Vec3 {
+        Vec3::new(0.0, 1.0, 0.0)
+    }
+}
+
+// the same color everywhere, black for a night scene or grey for a studio backdrop
+pub struct SolidColor {
+    pub color: Color3,
+}
+
+impl SolidColor {
+    pub fn new(color: Color3) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for SolidColor {
+    fn radiance(&self, _direction: &Vec3) -> Color3 {
+        self.color
+    }
+}
+
+// blends from `bottom` straight down to `top` straight up, `up` says which way is up
+pub struct Gradient {
+    pub bottom: Color3,
+    pub top: Color3,
+    pub up: Vec3,
+}
+
+impl Gradient {
+    pub fn new(bottom: Color3, top: Color3, up: Vec3) -> Self {
+        Self { bottom, top, up: up.unit_vector() }
+    }
+
+    // the white to blue sky everything used to get
+    pub fn sky() -> Self {
+        Self::new(Color3::new(1.0, 1.0, 1.0), Color3::new(0.5, 0.7, 1.0), Vec3::new(0.0, 1.0, 0.0))
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vec3) -> Color3 {
+        let a = 0.5 * (direction.dot(&self.up) + 1.0);
+        (1.0 - a) * self.bottom + a * self.top
+    }
+}
+
+// a lat-long (equirectangular) panorama. the middle of the image is straight down -z, the
+// left and right edges meet at +z, the top row is +y and the bottom row is -y. pixels are
+// linear radiance and get looked up with bilinear filtering
+//
+// it doubles as a light: pixels get picked in proportion to their luminance (times the
+// solid angle they cover, which shrinks towards the poles), so a small bright sun is found
+// by aiming at it instead of waiting for a bounce to wander into it
+pub struct LatLongImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color3>,
+    intensity: f64,
+    // turn around +y in degrees, counter clockwise looking down from above
+    rotation: f64,
+    rotation_sin: f64,
+    rotation_cos: f64,
+    distribution: Distribution2D,
+}
+
+impl LatLongImage {
+    // rows top to bottom like everything else here, all the same length
+    pub fn new(image: Vec<Vec<Color3>>, intensity: f64) -> Self {
+        let height = image.len();
+        let width = image.first().map_or(0, |row| row.len());
+        let pixels: Vec<Color3> = image.into_iter().flatten().collect();
+        assert!(width > 0 && pixels.len() == width * height, "environment image has to be a non-empty rectangle");
+
+        // nan or negative pixels would break the cdf
+        let luminances: Vec<f64> = pixels
+            .iter()
+            .map(|pixel| {
+                let l = luminance(*pixel);
+                if l.is_finite() { l.max(0.0) } else { 0.0 }
+            })
+            .collect();
+        // bilinear filtering bleeds every pixel into its neighbours, so each one gets the
+        // brightest of the 3x3 around it. otherwise the dim pixels next to the sun would
+        // show some of its light while hardly ever being picked
+        let mut weights = Vec::with_capacity(pixels.len());
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            for x in 0..width {
+                let mut brightest: f64 = 0.0;
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for dx in [width - 1, 0, 1] {
+                        brightest = brightest.max(luminances[ny * width + (x + dx) % width]);
+                    }
+                }
+                weights.push(brightest * sin_theta);
+            }
+        }
+        let distribution = Distribution2D::new(&weights, width);
+        Self {
+            width,
+            height,
+            pixels,
+            intensity,
+            rotation: 0.0,
+            rotation_sin: 0.0,
+            rotation_cos: 1.0,
+            distribution,
+        }
+    }
+
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees;
+        self.rotation_sin = degrees.to_radians().sin();
+        self.rotation_cos = degrees.to_radians().cos();
+        self
+    }
+
+    pub fn rotation(&self) -> f64 {
+        self.rotation
+    }
+
+    pub fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // u across, v down, both in [0, 1]
+    pub fn direction_to_uv(direction: &Vec3) -> (f64, f64) {
+        let u = 0.5 + direction.x().atan2(-direction.z()) / (2.0 * PI);
+        let v = direction.y().clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    pub fn uv_to_direction(u: f64, v: f64) -> Vec3 {
+        let phi = (u - 0.5) * 2.0 * PI;
+        let theta = v * PI;
+        Vec3::new(theta.sin() * phi.sin(), theta.cos(), -theta.sin() * phi.cos())
+    }
+
+    // world to image space is turning back by the rotation
+    fn to_image(&self, direction: &Vec3) -> Vec3 {
+        let (sin, cos) = (self.rotation_sin, self.rotation_cos);
+        Vec3::new(
+            cos * direction.x() - sin * direction.z(),
+            direction.y(),
+            sin * direction.x() + cos * direction.z(),
+        )
+    }
+
+    fn to_world(&self, direction: &Vec3) -> Vec3 {
+        let (sin, cos) = (self.rotation_sin, self.rotation_cos);
+        Vec3::new(
+            cos * direction.x() + sin * direction.z(),
+            direction.y(),
+            -sin * direction.x() + cos * direction.z(),
+        )
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> Color3 {
+        self.pixels[y * self.width + x]
+    }
+
+    // bilinear, wrapping around horizontally and clamping at the poles
+    fn lookup(&self, u: f64, v: f64) -> Color3 {
+        let x = u * self.width as f64 - 0.5;
+        let y = (v * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+        let x0 = (x0 as i64).rem_euclid(self.width as i64) as usize;
+        let x1 = (x0 + 1) % self.width;
+        let y0 = y0 as usize;
+        let y1 = (y0 + 1).min(self.height - 1);
+        let top = (1.0 - tx) * self.pixel(x0, y0) + tx * self.pixel(x1, y0);
+        let bottom = (1.0 - tx) * self.pixel(x0, y1) + tx * self.pixel(x1, y1);
+        (1.0 - ty) * top + ty * bottom
+    }
+}
+
+impl Environment for LatLongImage {
+    fn radiance(&self, direction: &Vec3) -> Color3 {
+        let (u, v) = Self::direction_to_uv(&self.to_image(direction));
+        self.intensity * self.lookup(u, v)
+    }
+
+    fn is_light(&self) -> bool {
+        self.intensity > 0.0 && self.distribution.integral() > 0.0
+    }
+
+    // the distribution is over the unit square, which maps onto the sphere with a
+    // jacobian of 2 pi^2 sin(theta)
+    fn pdf_value(&self, direction: &Vec3) -> f64 {
+        let (u, v) = Self::direction_to_uv(&self.to_image(&direction.unit_vector()));
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    fn random(&self, randomizer: &mut Rand) -> Vec3 {
+        let ((u, v), _) = self.distribution.sample(randomizer.next_f64(), randomizer.next_f64());
+        self.to_world(&Self::uv_to_direction(u, v))
+    }
+}
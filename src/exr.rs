@@ -0,0 +1,275 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::deflate::zlib_compress;
+use crate::vec3::Vec3;
+
+// openexr writer. single part scanline image with 32 bit float R, G and B channels, either
+// uncompressed or ZIP (zlib over 16 scanlines at a time)
+// https://openexr.com/en/latest/OpenEXRFileLayout.html
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Zip,
+}
+
+impl Compression {
+    fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Zip => 3,
+        }
+    }
+
+    fn lines_per_chunk(self) -> usize {
+        match self {
+            Compression::None => 1,
+            Compression::Zip => 16,
+        }
+    }
+}
+
+fn write_attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+fn header(width: usize, height: usize, compression: Compression) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC);
+    // version 2, plain single part scanline file
+    out.extend_from_slice(&2u32.to_le_bytes());
+
+    // channels have to be listed in alphabetical order
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]); // pLinear + reserved
+        channels.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        channels.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    channels.push(0);
+    write_attribute(&mut out, "channels", "chlist", &channels);
+    write_attribute(&mut out, "compression", "compression", &[compression.id()]);
+
+    let mut window = Vec::new();
+    for value in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    write_attribute(&mut out, "dataWindow", "box2i", &window);
+    write_attribute(&mut out, "displayWindow", "box2i", &window);
+    write_attribute(&mut out, "lineOrder", "lineOrder", &[0]); // increasing y
+    write_attribute(&mut out, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    write_attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(&mut out, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    out.push(0);
+    out
+}
+
+// what ZIP does before deflating: split the bytes into two interleaved halves, then store
+// each byte as the difference from the one before it
+fn zip_predict(data: &[u8]) -> Vec<u8> {
+    let mut reordered = Vec::with_capacity(data.len());
+    reordered.extend(data.iter().step_by(2));
+    reordered.extend(data.iter().skip(1).step_by(2));
+    let mut previous = reordered.first().copied().unwrap_or(0);
+    for byte in reordered.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    reordered
+}
+
+pub fn write_to_exr(filename: &str, image: &[Vec<Vec3>], compression: Compression) -> io::Result<()> {
+    let height = image.len();
+    let width = image.first().map_or(0, |row| row.len());
+
+    // every chunk is a run of scanlines, each stored channel by channel
+    let lines_per_chunk = compression.lines_per_chunk();
+    let mut chunks = Vec::new();
+    for (chunk_index, rows) in image.chunks(lines_per_chunk).enumerate() {
+        let mut raw = Vec::with_capacity(rows.len() * width * 12);
+        for row in rows {
+            for channel in [2, 1, 0] {
+                for pixel in row {
+                    raw.extend_from_slice(&(pixel[channel] as f32).to_le_bytes());
+                }
+            }
+        }
+        let data = match compression {
+            Compression::None => raw,
+            Compression::Zip => {
+                // readers take a chunk that isn't smaller than the raw size as uncompressed
+                let compressed = zlib_compress(&zip_predict(&raw));
+                if compressed.len() < raw.len() { compressed } else { raw }
+            }
+        };
+        let mut chunk = Vec::with_capacity(data.len() + 8);
+        chunk.extend_from_slice(&((chunk_index * lines_per_chunk) as i32).to_le_bytes());
+        chunk.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        chunk.extend_from_slice(&data);
+        chunks.push(chunk);
+    }
+
+    let header = header(width, height, compression);
+    let mut file = BufWriter::new(File::create(filename)?);
+    file.write_all(&header)?;
+    // offset table, where every chunk starts counting from the beginning of the file
+    let mut offset = (header.len() + 8 * chunks.len()) as u64;
+    for chunk in &chunks {
+        file.write_all(&offset.to_le_bytes())?;
+        offset += chunk.len() as u64;
+    }
+    for chunk in &chunks {
+        file.write_all(chunk)?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::tests::zlib_decompress;
+    use crate::rand::Rand;
+    use std::fs;
+
+    fn le32(bytes: &[u8]) -> u32 {
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    // undoes zip_predict: sum the differences back up, then interleave the two halves
+    fn unpredict(data: &[u8]) -> Vec<u8> {
+        let mut summed = data.to_vec();
+        for i in 1..summed.len() {
+            summed[i] = summed[i - 1].wrapping_add(summed[i]).wrapping_sub(128);
+        }
+        let (evens, odds) = summed.split_at(summed.len().div_ceil(2));
+        let mut out = Vec::with_capacity(data.len());
+        for (i, &even) in evens.iter().enumerate() {
+            out.push(even);
+            if let Some(&odd) = odds.get(i) {
+                out.push(odd);
+            }
+        }
+        out
+    }
+
+    // reads back the one kind of file we write. gives the compression id and the pixels
+    fn decode(file: &[u8]) -> (u8, Vec<Vec<[f32; 3]>>) {
+        assert_eq!(file[..4], MAGIC);
+        assert_eq!(le32(&file[4..]), 2);
+        let mut position = 8;
+        let mut compression = None;
+        let mut window = None;
+        while file[position] != 0 {
+            let name_end = position + file[position..].iter().position(|&byte| byte == 0).unwrap();
+            let kind_end = name_end + 1 + file[name_end + 1..].iter().position(|&byte| byte == 0).unwrap();
+            let size = le32(&file[kind_end + 1..]) as usize;
+            let value = &file[kind_end + 5..kind_end + 5 + size];
+            match &file[position..name_end] {
+                b"compression" => compression = Some(value[0]),
+                b"dataWindow" => window = Some((le32(&value[8..]) as usize + 1, le32(&value[12..]) as usize + 1)),
+                _ => {}
+            }
+            position = kind_end + 5 + size;
+        }
+        position += 1;
+        let (compression, (width, height)) = (compression.unwrap(), window.unwrap());
+        let lines_per_chunk = if compression == 3 { 16 } else { 1 };
+
+        let chunk_count = height.div_ceil(lines_per_chunk);
+        let mut image = Vec::new();
+        for chunk in 0..chunk_count {
+            let offset = u64::from_le_bytes(file[position + 8 * chunk..position + 8 * chunk + 8].try_into().unwrap());
+            let offset = offset as usize;
+            assert_eq!(le32(&file[offset..]) as usize, chunk * lines_per_chunk, "chunk starts at the wrong line");
+            let size = le32(&file[offset + 4..]) as usize;
+            let data = &file[offset + 8..offset + 8 + size];
+            let lines = lines_per_chunk.min(height - chunk * lines_per_chunk);
+            let raw_size = lines * width * 12;
+            let raw = if size < raw_size { unpredict(&zlib_decompress(data)) } else { data.to_vec() };
+            assert_eq!(raw.len(), raw_size);
+            for line in raw.chunks(width * 12) {
+                let channel = |c: usize, x: usize| f32::from_le_bytes(line[4 * (c * width + x)..][..4].try_into().unwrap());
+                // stored b, g, r
+                image.push((0..width).map(|x| [channel(2, x), channel(1, x), channel(0, x)]).collect());
+            }
+        }
+        (compression, image)
+    }
+
+    // a smooth gradient that zip squeezes down, with noise rows it can't do anything with
+    fn test_image(width: usize, height: usize) -> Vec<Vec<Vec3>> {
+        let mut randomizer = Rand::new_with_seed(3);
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        if y % 7 == 3 {
+                            Vec3::new(randomizer.next_f64(), 100.0 * randomizer.next_f64(), -randomizer.next_f64())
+                        } else {
+                            Vec3::new(x as f64 / width as f64, y as f64 * 0.25, 2.5)
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn round_trip(compression: Compression, width: usize, height: usize) {
+        let image = test_image(width, height);
+        let path = std::env::temp_dir().join(format!("raytracer-{}-{:?}-{}.exr", std::process::id(), compression, width));
+        let filename = path.to_str().unwrap();
+        write_to_exr(filename, &image, compression).unwrap();
+        let file = fs::read(filename);
+        fs::remove_file(filename).unwrap();
+        let (id, read) = decode(&file.unwrap());
+        assert_eq!(id, compression.id());
+        let expected: Vec<Vec<[f32; 3]>> =
+            image.iter().map(|row| row.iter().map(|p| [p.x() as f32, p.y() as f32, p.z() as f32]).collect()).collect();
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn round_trips_uncompressed() {
+        round_trip(Compression::None, 13, 9);
+    }
+
+    // 40 lines is two full zip chunks and a short one
+    #[test]
+    fn round_trips_zip() {
+        round_trip(Compression::Zip, 64, 40);
+        round_trip(Compression::Zip, 1, 1);
+    }
+
+    // a chunk that would only get bigger is stored raw, which readers can tell from its size
+    #[test]
+    fn zip_falls_back_to_raw_for_noise() {
+        let mut randomizer = Rand::new_with_seed(9);
+        let row: Vec<Vec3> = (0..32).map(|_| Vec3::new(randomizer.next_f64(), randomizer.next_f64(), randomizer.next_f64())).collect();
+        let image = vec![row; 1];
+        let path = std::env::temp_dir().join(format!("raytracer-{}-noise.exr", std::process::id()));
+        let filename = path.to_str().unwrap();
+        write_to_exr(filename, &image, Compression::Zip).unwrap();
+        let file = fs::read(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+        let raw: Vec<u8> = [2, 1, 0]
+            .iter()
+            .flat_map(|&channel| image[0].iter().flat_map(move |pixel| (pixel[channel] as f32).to_le_bytes()))
+            .collect();
+        assert!(file.ends_with(&raw), "the noise should have been stored as is");
+        let (_, read) = decode(&file);
+        assert_eq!(read[0][5], [image[0][5].x() as f32, image[0][5].y() as f32, image[0][5].z() as f32]);
+    }
+}
@@ -0,0 +1,912 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::json::{self, Json};
+use crate::mat4::Mat4;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::{Face, Mesh};
+use crate::object::{Hittable, Sphere};
+use crate::vec3::{Color3, Point3, Vec3};
+
+// gltf 2.0 loader, both the .gltf json (with .bin files or base64 data uris next to it) and
+// the single file .glb. the node hierarchy gets baked, every mesh a node uses is put into
+// world space and added to one big mesh whose groups are the node names
+// https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+//
+// what comes across:
+//   triangle, strip and fan primitives with POSITION, NORMAL and TEXCOORD_0
+//   perspective cameras (orthographic ones are skipped, the camera can't do those)
+//   metallic-roughness materials, by their factors only since textures aren't supported:
+//     emissiveFactor (times KHR_materials_emissive_strength) above zero -> DiffuseLight
+//     KHR_materials_transmission above 0.5                            -> Dielectric(baseColor, ior)
+//     metallicFactor >= 0.5                                           -> Metal(baseColor, roughness)
+//     anything else                                                   -> Lambertian(baseColor)
+//   KHR_lights_punctual lights, see `light_spheres`
+
+pub struct Gltf {
+    pub mesh: Mesh,
+    // small glowing spheres standing in for the punctual lights
+    pub lights: Vec<Box<dyn Hittable>>,
+    // in the order the nodes were visited, the first one is usually the one meant
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl Gltf {
+    pub fn into_hittables(self) -> Vec<Box<dyn Hittable>> {
+        let mut objects = self.mesh.into_hittables();
+        objects.extend(self.lights);
+        objects
+    }
+}
+
+// a perspective camera placed by its node, already in world space
+#[derive(Clone, Copy, Debug)]
+pub struct GltfCamera {
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub vup: Vec3,
+    // degrees, like Camera takes it
+    pub vfov: f64,
+    pub aspect_ratio: Option<f64>,
+}
+
+impl GltfCamera {
+    // `aspect_ratio` is only used when the file doesn't say
+    pub fn to_camera(&self, aspect_ratio: f64, image_width: u32) -> Camera {
+        Camera::new(
+            self.aspect_ratio.unwrap_or(aspect_ratio),
+            image_width,
+            self.look_from,
+            self.look_at,
+            self.vup,
+            self.vfov,
+        )
+    }
+}
+
+enum LightKind {
+    Point,
+    Directional,
+}
+
+// a punctual light once its node has placed it
+struct Light {
+    kind: LightKind,
+    position: Point3,
+    // the way the light travels, for directional ones
+    direction: Vec3,
+    // color times intensity
+    power: Color3,
+}
+
+fn invalid_gltf(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("not a usable gltf: {}", message))
+}
+
+// primitives without a material get `default_material`. .bin files and other uris are
+// looked up next to the file
+pub fn load(path: &str, default_material: Arc<dyn Material>) -> io::Result<Gltf> {
+    let data = fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    parse(&data, directory, default_material).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+// takes either the json text or a whole .glb, whichever `data` turns out to be
+pub fn parse(data: &[u8], directory: &Path, default_material: Arc<dyn Material>) -> io::Result<Gltf> {
+    let (text, binary) = if data.starts_with(b"glTF") { split_glb(data)? } else { (data, None) };
+    let text = std::str::from_utf8(text).map_err(|_| invalid_gltf("the json isn't utf-8"))?;
+    let root = json::parse(text)?;
+    let version = root.get("asset").and_then(|asset| asset.get("version"));
+    match version {
+        Some(version) if version.as_str()?.starts_with("2.") => {}
+        Some(version) => return Err(version.error(format!("only gltf 2.x is supported, not {}", version.as_str()?)).into()),
+        None => return Err(root.error("missing asset.version").into()),
+    }
+
+    let buffers = load_buffers(&root, binary, directory)?;
+    let mut mesh = Mesh::new();
+    mesh.materials.push(default_material);
+    for material in array(&root, "materials")? {
+        mesh.materials.push(parse_material(material)?);
+    }
+
+    let mut cameras = Vec::new();
+    let mut lights = Vec::new();
+    let nodes = array(&root, "nodes")?;
+    let mut stack: Vec<(usize, Mat4, usize)> = root_nodes(&root, nodes.len())?
+        .into_iter()
+        .rev()
+        .map(|node| (node, Mat4::IDENTITY, 0))
+        .collect();
+    while let Some((index, parent, depth)) = stack.pop() {
+        let node = &nodes[index];
+        if depth > nodes.len() {
+            return Err(node.error("the node hierarchy loops back on itself").into());
+        }
+        let world = parent * local_matrix(node)?;
+        if let Some(mesh_index) = node.get("mesh") {
+            let name = match node.get("name") {
+                Some(name) => name.as_str()?.to_string(),
+                None => format!("node {}", index),
+            };
+            let group = match mesh.groups.iter().position(|existing| *existing == name) {
+                Some(group) => group,
+                None => {
+                    mesh.groups.push(name);
+                    mesh.groups.len() - 1
+                }
+            };
+            let gltf_mesh = item(&root, "meshes", mesh_index)?;
+            for primitive in field(gltf_mesh, "primitives")?.as_array()? {
+                add_primitive(&root, &buffers, primitive, &world, group, &mut mesh)?;
+            }
+        }
+        if let Some(camera) = node.get("camera") {
+            cameras.extend(parse_camera(item(&root, "cameras", camera)?, &world)?);
+        }
+        if let Some(light) = node.get("extensions").and_then(|extensions| extensions.get("KHR_lights_punctual")) {
+            let lights_json = root
+                .get("extensions")
+                .and_then(|extensions| extensions.get("KHR_lights_punctual"))
+                .ok_or_else(|| light.error("no KHR_lights_punctual lights at the top level"))?;
+            lights.extend(parse_light(item(lights_json, "lights", field(light, "light")?)?, &world)?);
+        }
+        if let Some(children) = node.get("children") {
+            for child in children.as_array()?.iter().rev() {
+                stack.push((index_into(child, nodes.len(), "nodes")?, world, depth + 1));
+            }
+        }
+    }
+
+    let lights = light_spheres(&lights, &mesh);
+    Ok(Gltf { mesh, lights, cameras })
+}
+
+// a .glb is a 12 byte header and then chunks, the json one first and an optional binary one
+fn split_glb(data: &[u8]) -> io::Result<(&[u8], Option<&[u8]>)> {
+    let word = |offset: usize| -> io::Result<u32> {
+        let bytes = data.get(offset..offset + 4).ok_or_else(|| invalid_gltf("glb is truncated"))?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    if word(4)? != 2 {
+        return Err(invalid_gltf(&format!("unsupported glb version {}", word(4)?)));
+    }
+    let length = (word(8)? as usize).min(data.len());
+    let mut chunks = Vec::new();
+    let mut position = 12;
+    while position + 8 <= length {
+        let chunk_length = word(position)? as usize;
+        let kind = word(position + 4)?;
+        let start = position + 8;
+        let chunk = data
+            .get(start..start + chunk_length)
+            .ok_or_else(|| invalid_gltf("glb chunk runs past the end of the file"))?;
+        chunks.push((kind, chunk));
+        position = start + chunk_length;
+    }
+    match chunks.as_slice() {
+        [(0x4e4f534a, text), rest @ ..] => {
+            let binary = rest.iter().find(|(kind, _)| *kind == 0x004e4942).map(|(_, chunk)| *chunk);
+            Ok((text, binary))
+        }
+        _ => Err(invalid_gltf("glb doesn't start with a json chunk")),
+    }
+}
+
+fn load_buffers(root: &Json, binary: Option<&[u8]>, directory: &Path) -> io::Result<Vec<Vec<u8>>> {
+    let mut buffers = Vec::new();
+    for (index, buffer) in array(root, "buffers")?.iter().enumerate() {
+        let length = field(buffer, "byteLength")?.as_u64()? as usize;
+        let data = match buffer.get("uri") {
+            Some(uri_json) => {
+                let uri = uri_json.as_str()?;
+                if let Some(data) = uri.strip_prefix("data:") {
+                    let (_, encoded) = data
+                        .split_once(";base64,")
+                        .ok_or_else(|| uri_json.error("only base64 data uris are supported"))?;
+                    base64(encoded).ok_or_else(|| uri_json.error("bad base64 in the data uri"))?
+                } else {
+                    let path = directory.join(percent_decode(uri));
+                    fs::read(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?
+                }
+            }
+            // only the first buffer of a .glb can leave out the uri, it's the binary chunk
+            None if index == 0 && binary.is_some() => binary.unwrap_or_default().to_vec(),
+            None => return Err(buffer.error("buffer has no uri and there's no glb binary chunk").into()),
+        };
+        if data.len() < length {
+            return Err(buffer.error(format!("buffer should be {} bytes, only found {}", length, data.len())).into());
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+fn base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for c in text.bytes().filter(|&c| c != b'=' && !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+// uris are allowed to escape things like spaces as %20
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// the nodes of the default scene, or every node nothing else has as a child if there are
+// no scenes
+fn root_nodes(root: &Json, count: usize) -> io::Result<Vec<usize>> {
+    let scenes = array(root, "scenes")?;
+    if !scenes.is_empty() {
+        let scene = match root.get("scene") {
+            Some(scene) => item(root, "scenes", scene)?,
+            None => &scenes[0],
+        };
+        return match scene.get("nodes") {
+            Some(nodes) => nodes.as_array()?.iter().map(|node| index_into(node, count, "nodes")).collect(),
+            None => Ok(Vec::new()),
+        };
+    }
+    let mut is_child = vec![false; count];
+    for node in array(root, "nodes")? {
+        if let Some(children) = node.get("children") {
+            for child in children.as_array()? {
+                is_child[index_into(child, count, "nodes")?] = true;
+            }
+        }
+    }
+    Ok((0..count).filter(|&node| !is_child[node]).collect())
+}
+
+fn parse_material(material: &Json) -> io::Result<Arc<dyn Material>> {
+    let extension = |name: &str| material.get("extensions").and_then(|extensions| extensions.get(name));
+    let mut emission = color(material.get("emissiveFactor"), Color3::new(0.0, 0.0, 0.0))?;
+    if let Some(strength) = extension("KHR_materials_emissive_strength") {
+        emission = number(strength, "emissiveStrength", 1.0)? * emission;
+    }
+    if emission.x() > 0.0 || emission.y() > 0.0 || emission.z() > 0.0 {
+        return Ok(Arc::new(DiffuseLight::new(emission)));
+    }
+
+    let pbr = material.get("pbrMetallicRoughness");
+    let base_color = color(pbr.and_then(|pbr| pbr.get("baseColorFactor")), Color3::new(1.0, 1.0, 1.0))?;
+    let transmission = match extension("KHR_materials_transmission") {
+        Some(transmission) => number(transmission, "transmissionFactor", 0.0)?,
+        None => 0.0,
+    };
+    if transmission > 0.5 {
+        let ior = match extension("KHR_materials_ior") {
+            Some(ior) => number(ior, "ior", 1.5)?,
+            None => 1.5,
+        };
+        return Ok(Arc::new(Dielectric::new(base_color, ior.max(1.0))));
+    }
+    let (metallic, roughness) = match pbr {
+        Some(pbr) => (number(pbr, "metallicFactor", 1.0)?, number(pbr, "roughnessFactor", 1.0)?),
+        None => (1.0, 1.0),
+    };
+    if metallic >= 0.5 {
+        return Ok(Arc::new(Metal::new(base_color, roughness.clamp(0.0, 1.0))));
+    }
+    Ok(Arc::new(Lambertian::new(base_color)))
+}
+
+// rgb, or rgba with the alpha dropped
+fn color(json: Option<&Json>, default: Color3) -> io::Result<Color3> {
+    let Some(json) = json else {
+        return Ok(default);
+    };
+    let values = json.as_array()?;
+    if values.len() != 3 && values.len() != 4 {
+        return Err(json.error(format!("expected 3 or 4 numbers, found {}", values.len())).into());
+    }
+    let channel = |i: usize| -> io::Result<f64> {
+        let value = values[i].as_f64()?;
+        if value < 0.0 {
+            return Err(values[i].error("colors can't be negative").into());
+        }
+        Ok(value)
+    };
+    Ok(Color3::new(channel(0)?, channel(1)?, channel(2)?))
+}
+
+fn add_primitive(
+    root: &Json,
+    buffers: &[Vec<u8>],
+    primitive: &Json,
+    world: &Mat4,
+    group: usize,
+    mesh: &mut Mesh,
+) -> io::Result<()> {
+    let mode = match primitive.get("mode") {
+        Some(mode) => mode.as_u64()?,
+        None => 4,
+    };
+    // points and lines have no area to hit
+    if mode < 4 {
+        return Ok(());
+    }
+    let attributes = field(primitive, "attributes")?;
+    let (positions, _) = read_accessor(root, buffers, field(attributes, "POSITION")?, 3)?;
+    let count = positions.len() / 3;
+    let normals = match attributes.get("NORMAL") {
+        Some(accessor) => Some(read_accessor(root, buffers, accessor, 3)?.0),
+        None => None,
+    };
+    let uvs = match attributes.get("TEXCOORD_0") {
+        Some(accessor) => Some(read_accessor(root, buffers, accessor, 2)?.0),
+        None => None,
+    };
+    let indices: Vec<usize> = match primitive.get("indices") {
+        Some(accessor) => {
+            let (indices, json) = read_accessor(root, buffers, accessor, 1)?;
+            indices
+                .into_iter()
+                .map(|index| {
+                    if index < 0.0 || index as usize >= count {
+                        return Err(json.error(format!("index {} is out of range, there are {} vertices", index, count)).into());
+                    }
+                    Ok(index as usize)
+                })
+                .collect::<io::Result<_>>()?
+        }
+        None => (0..count).collect(),
+    };
+    let material = match primitive.get("material") {
+        Some(material) => index_into(material, mesh.materials.len() - 1, "materials")? + 1,
+        None => 0,
+    };
+
+    let triangles: Vec<[usize; 3]> = match mode {
+        4 => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+        // every other strip triangle is wound backwards and has to be turned around
+        5 => (2..indices.len())
+            .map(|i| {
+                if i % 2 == 0 {
+                    [indices[i - 2], indices[i - 1], indices[i]]
+                } else {
+                    [indices[i - 1], indices[i - 2], indices[i]]
+                }
+            })
+            .collect(),
+        6 => (2..indices.len()).map(|i| [indices[0], indices[i - 1], indices[i]]).collect(),
+        _ => return Err(primitive.error(format!("unknown primitive mode {}", mode)).into()),
+    };
+
+    let base = (mesh.positions.len(), mesh.normals.len(), mesh.uvs.len());
+    for vertex in positions.chunks_exact(3) {
+        mesh.positions.push(world.transform_point(&Point3::new(vertex[0], vertex[1], vertex[2])));
+    }
+    if let Some(normals) = &normals {
+        let normal_matrix = world.normal_matrix();
+        for normal in normals.chunks_exact(3) {
+            let normal = normal_matrix.transform_vector(&Vec3::new(normal[0], normal[1], normal[2]));
+            mesh.normals.push(if normal.near_zero() { normal } else { normal.unit_vector() });
+        }
+    }
+    if let Some(uvs) = &uvs {
+        // gltf puts v = 0 at the top of the texture, everything else here has it at the bottom
+        mesh.uvs.extend(uvs.chunks_exact(2).map(|uv| (uv[0], 1.0 - uv[1])));
+    }
+    // a mirroring transform turns the winding inside out, so swap it back
+    let mirrored = world.determinant() < 0.0;
+    let has_normals = normals.as_ref().is_some_and(|normals| normals.len() / 3 == count);
+    let has_uvs = uvs.as_ref().is_some_and(|uvs| uvs.len() / 2 == count);
+    for triangle in triangles {
+        let triangle = if mirrored { [triangle[0], triangle[2], triangle[1]] } else { triangle };
+        mesh.faces.push(Face {
+            positions: triangle.map(|i| base.0 + i),
+            normals: has_normals.then(|| triangle.map(|i| base.1 + i)),
+            uvs: has_uvs.then(|| triangle.map(|i| base.2 + i)),
+            material,
+            group,
+        });
+    }
+    Ok(())
+}
+
+// reads an accessor into floats, `components` per element (only the first that many are kept
+// if it has more). normalized integers come back in [0, 1] or [-1, 1]. also hands back the
+// accessor for error messages
+fn read_accessor<'a>(root: &'a Json, buffers: &[Vec<u8>], index: &Json, components: usize) -> io::Result<(Vec<f64>, &'a Json)> {
+    let accessor = item(root, "accessors", index)?;
+    let count = field(accessor, "count")?.as_u64()? as usize;
+    let kind_json = field(accessor, "type")?;
+    let available = match kind_json.as_str()? {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        other => return Err(kind_json.error(format!("can't use a {} accessor here", other)).into()),
+    };
+    if available < components {
+        return Err(kind_json.error(format!("expected at least {} components, found {}", components, available)).into());
+    }
+    let component_type = field(accessor, "componentType")?;
+    let (size, normalizer): (usize, f64) = match component_type.as_u64()? {
+        5120 => (1, 127.0),
+        5121 => (1, 255.0),
+        5122 => (2, 32767.0),
+        5123 => (2, 65535.0),
+        5125 => (4, u32::MAX as f64),
+        5126 => (4, 1.0),
+        other => return Err(component_type.error(format!("unknown component type {}", other)).into()),
+    };
+    let kind = component_type.as_u64()?;
+    let normalized = match accessor.get("normalized") {
+        Some(normalized) => normalized.as_bool()?,
+        None => false,
+    };
+    if accessor.get("sparse").is_some() {
+        return Err(accessor.error("sparse accessors aren't supported").into());
+    }
+    // counts come straight from the file, so every size worked out from them is checked
+    let too_big = || invalid_gltf("accessor is too big");
+    let total = count.checked_mul(components).ok_or_else(too_big)?;
+    // allocation failures come back as an error too, rather than aborting
+    let mut values: Vec<f64> = Vec::new();
+    // no buffer view means all zeros
+    let Some(view_index) = accessor.get("bufferView") else {
+        values.try_reserve_exact(total).map_err(|_| too_big())?;
+        values.resize(total, 0.0);
+        return Ok((values, accessor));
+    };
+
+    let view = item(root, "bufferViews", view_index)?;
+    let buffer = &buffers[index_into(field(view, "buffer")?, buffers.len(), "buffers")?];
+    let view_offset = optional_usize(view, "byteOffset")?;
+    let view_length = field(view, "byteLength")?.as_u64()? as usize;
+    let element_size = size * available;
+    let stride = match view.get("byteStride") {
+        Some(stride) => stride.as_u64()? as usize,
+        None => element_size,
+    };
+    let start = view_offset.checked_add(optional_usize(accessor, "byteOffset")?).ok_or_else(too_big)?;
+    let end = match count {
+        0 => Some(start),
+        _ => stride.checked_mul(count - 1).and_then(|span| span.checked_add(start)).and_then(|end| end.checked_add(element_size)),
+    }
+    .ok_or_else(too_big)?;
+    let view_end = view_offset.checked_add(view_length).ok_or_else(too_big)?;
+    if end > view_end || view_end > buffer.len() {
+        return Err(accessor.error("accessor runs past the end of its buffer").into());
+    }
+    values.try_reserve_exact(total).map_err(|_| too_big())?;
+
+    for element in 0..count {
+        for component in 0..components {
+            let offset = start + element * stride + component * size;
+            let bytes = &buffer[offset..offset + size];
+            let raw = match kind {
+                5120 => bytes[0] as i8 as f64,
+                5121 => bytes[0] as f64,
+                5122 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                5123 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                5125 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+                _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            };
+            let value = if normalized && kind != 5126 { (raw / normalizer).max(-1.0) } else { raw };
+            if !value.is_finite() {
+                return Err(accessor.error("accessor has a value that isn't a finite number").into());
+            }
+            values.push(value);
+        }
+    }
+    Ok((values, accessor))
+}
+
+fn parse_camera(camera: &Json, world: &Mat4) -> io::Result<Option<GltfCamera>> {
+    let Some(perspective) = camera.get("perspective") else {
+        return Ok(None);
+    };
+    let yfov = field(perspective, "yfov")?.as_f64()?;
+    if yfov <= 0.0 || yfov >= std::f64::consts::PI {
+        return Err(field(perspective, "yfov")?.error("yfov has to be between 0 and pi").into());
+    }
+    let aspect_ratio = match perspective.get("aspectRatio") {
+        Some(aspect_ratio) => Some(aspect_ratio.as_f64()?).filter(|&aspect_ratio| aspect_ratio > 0.0),
+        None => None,
+    };
+    // cameras look down their -z with +y up
+    let look_from = world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+    let forward = world.transform_vector(&Vec3::new(0.0, 0.0, -1.0));
+    let vup = world.transform_vector(&Vec3::new(0.0, 1.0, 0.0));
+    if forward.near_zero() || vup.cross(&forward).near_zero() {
+        return Ok(None);
+    }
+    Ok(Some(GltfCamera {
+        look_from,
+        look_at: look_from + forward.unit_vector(),
+        vup,
+        vfov: yfov.to_degrees(),
+        aspect_ratio,
+    }))
+}
+
+fn parse_light(light: &Json, world: &Mat4) -> io::Result<Option<Light>> {
+    let power = number(light, "intensity", 1.0)? * color(light.get("color"), Color3::new(1.0, 1.0, 1.0))?;
+    let kind_json = field(light, "type")?;
+    let kind = match kind_json.as_str()? {
+        // spot lights lose their cone and shine every way
+        "point" | "spot" => LightKind::Point,
+        "directional" => LightKind::Directional,
+        other => return Err(kind_json.error(format!("unknown light type \"{}\"", other)).into()),
+    };
+    let direction = world.transform_vector(&Vec3::new(0.0, 0.0, -1.0));
+    if direction.near_zero() {
+        return Ok(None);
+    }
+    Ok(Some(Light {
+        kind,
+        position: world.transform_point(&Point3::new(0.0, 0.0, 0.0)),
+        direction: direction.unit_vector(),
+        power,
+    }))
+}
+
+// there's nothing infinitely small to hit, so punctual lights become little emissive spheres,
+// sized off the scene so they stay small next to it. a point light of intensity I (candela,
+// taken as our radiance units) becomes a sphere of radius r glowing with I / (pi r^2), which
+// sends the same I towards anything far enough away. directional lights become a sun: a
+// sphere way out past the scene covering half a degree, glowing with E / (pi sin^2 half-angle)
+// so it lands the same irradiance E
+fn light_spheres(lights: &[Light], mesh: &Mesh) -> Vec<Box<dyn Hittable>> {
+    let (center, size) = if mesh.positions.is_empty() {
+        (Point3::new(0.0, 0.0, 0.0), 1.0)
+    } else {
+        let bounds = mesh.bounding_box();
+        (bounds.centroid(), bounds.extent().length().max(1e-3))
+    };
+    let point_radius = 0.01 * size;
+    let sun_distance = 100.0 * size;
+    let sun_half_angle = 0.25f64.to_radians();
+    lights
+        .iter()
+        .map(|light| -> Box<dyn Hittable> {
+            match light.kind {
+                LightKind::Point => {
+                    let radiance = light.power / (std::f64::consts::PI * point_radius * point_radius);
+                    Box::new(Sphere::new(light.position, point_radius, Arc::new(DiffuseLight::new(radiance))))
+                }
+                LightKind::Directional => {
+                    let sin = sun_half_angle.sin();
+                    let radiance = light.power / (std::f64::consts::PI * sin * sin);
+                    Box::new(Sphere::new(
+                        center - sun_distance * light.direction,
+                        sun_distance * sin,
+                        Arc::new(DiffuseLight::new(radiance)),
+                    ))
+                }
+            }
+        })
+        .collect()
+}
+
+// gltf json helpers. missing arrays are empty, the way the spec treats them
+fn array<'a>(json: &'a Json, key: &str) -> io::Result<&'a [Json]> {
+    match json.get(key) {
+        Some(value) => Ok(value.as_array()?),
+        None => Ok(&[]),
+    }
+}
+
+fn field<'a>(json: &'a Json, key: &str) -> io::Result<&'a Json> {
+    json.get(key).ok_or_else(|| json.error(format!("missing \"{}\"", key)).into())
+}
+
+fn number(json: &Json, key: &str, default: f64) -> io::Result<f64> {
+    match json.get(key) {
+        Some(value) => Ok(value.as_f64()?),
+        None => Ok(default),
+    }
+}
+
+fn optional_usize(json: &Json, key: &str) -> io::Result<usize> {
+    match json.get(key) {
+        Some(value) => Ok(value.as_u64()? as usize),
+        None => Ok(0),
+    }
+}
+
+fn index_into(index: &Json, count: usize, what: &str) -> io::Result<usize> {
+    let value = index.as_u64()? as usize;
+    if value >= count {
+        return Err(index.error(format!("there's no {} {}, only {} of them", what, value, count)).into());
+    }
+    Ok(value)
+}
+
+// root.key[index]
+fn item<'a>(root: &'a Json, key: &str, index: &Json) -> io::Result<&'a Json> {
+    let items = array(root, key)?;
+    Ok(&items[index_into(index, items.len(), key)?])
+}
+
+// "matrix" if the node has one (written out column by column), otherwise translation *
+// rotation * scale
+fn local_matrix(node: &Json) -> io::Result<Mat4> {
+    let numbers = |key: &str, default: &[f64]| -> io::Result<Vec<f64>> {
+        let Some(json) = node.get(key) else {
+            return Ok(default.to_vec());
+        };
+        let values = json.as_array()?.iter().map(|value| value.as_f64()).collect::<Result<Vec<f64>, _>>()?;
+        if values.len() != default.len() {
+            return Err(json.error(format!("expected {} numbers, found {}", default.len(), values.len())).into());
+        }
+        Ok(values)
+    };
+    if node.get("matrix").is_some() {
+        let m = numbers("matrix", &[0.0; 16])?;
+        let mut columns = [[0.0; 4]; 4];
+        for (column, values) in columns.iter_mut().zip(m.chunks_exact(4)) {
+            column.copy_from_slice(values);
+        }
+        return Ok(Mat4::from_columns(columns));
+    }
+    let t = numbers("translation", &[0.0, 0.0, 0.0])?;
+    let q = numbers("rotation", &[0.0, 0.0, 0.0, 1.0])?;
+    let s = numbers("scale", &[1.0, 1.0, 1.0])?;
+    Ok(Mat4::translation(Vec3::new(t[0], t[1], t[2]))
+        * Mat4::from_quaternion(q[0], q[1], q[2], q[3])
+        * Mat4::scaling(Vec3::new(s[0], s[1], s[2])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+        for chunk in bytes.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    text.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+                } else {
+                    text.push('=');
+                }
+            }
+        }
+        text
+    }
+
+    // a unit square as 4 float positions, 6 u16 indices and normalized u8 uvs padded out to
+    // a 4 byte stride
+    fn buffer() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for position in [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]] {
+            for value in position {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for index in [0u16, 1, 2, 2, 1, 3] {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        for uv in [[0u8, 0], [255, 0], [0, 255], [255, 255]] {
+            bytes.extend_from_slice(&[uv[0], uv[1], 0xee, 0xee]);
+        }
+        bytes
+    }
+
+    // the square three times: as indexed triangles on a parent node moved 5 along z, as a
+    // strip on a mirrored child and as a fan on a child moved 10 along x, which also has a
+    // camera below it. the parent carries a point light
+    const SCENE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [
+            { "name": "parent", "mesh": 0, "translation": [0, 0, 5], "children": [1, 2],
+              "extensions": { "KHR_lights_punctual": { "light": 0 } } },
+            { "name": "strip", "mesh": 1, "scale": [-1, 1, 1] },
+            { "name": "fan", "mesh": 2, "translation": [10, 0, 0], "children": [3] },
+            { "camera": 0, "translation": [0, 0, 10] }
+        ],
+        "meshes": [
+            { "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 2 }, "indices": 1, "material": 0 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 5, "material": 1 }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 6, "material": 2 }] }
+        ],
+        "materials": [
+            { "pbrMetallicRoughness": { "baseColorFactor": [0.8, 0.1, 0.1, 1], "metallicFactor": 0 } },
+            { "emissiveFactor": [1, 1, 1], "extensions": { "KHR_materials_emissive_strength": { "emissiveStrength": 4 } } },
+            { "pbrMetallicRoughness": { "metallicFactor": 1, "roughnessFactor": 0.2 } }
+        ],
+        "cameras": [{ "type": "perspective", "perspective": { "yfov": 0.8, "aspectRatio": 1.5, "znear": 0.1 } }],
+        "extensions": { "KHR_lights_punctual": { "lights": [{ "type": "point", "intensity": 3 }] } },
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5123, "count": 6, "type": "SCALAR" },
+            { "bufferView": 2, "componentType": 5121, "normalized": true, "count": 4, "type": "VEC2" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 48 },
+            { "buffer": 0, "byteOffset": 48, "byteLength": 12 },
+            { "buffer": 0, "byteOffset": 60, "byteLength": 16, "byteStride": 4 }
+        ],
+        "buffers": [{ "byteLength": 76, "uri": "data:application/octet-stream;base64,BUFFER" }]
+    }"#;
+
+    fn scene() -> String {
+        SCENE.replace("BUFFER", &encode_base64(&buffer()))
+    }
+
+    fn parse_text(text: &str) -> io::Result<Gltf> {
+        let default: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)));
+        parse(text.as_bytes(), Path::new(""), default)
+    }
+
+    fn assert_near(a: Vec3, b: [f64; 3]) {
+        assert!((a - Vec3::from(b)).length() < 1e-9, "expected {:?}, got {:?}", b, a);
+    }
+
+    #[test]
+    fn base64_round_trips() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", &buffer()] {
+            assert_eq!(base64(&encode_base64(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(base64("Zm9v\nYmE=").unwrap(), b"fooba");
+        assert!(base64("Zm9v!").is_none());
+    }
+
+    #[test]
+    fn bakes_the_node_hierarchy() {
+        let gltf = parse_text(&scene()).unwrap();
+        let mesh = &gltf.mesh;
+        assert_eq!(mesh.groups, ["parent", "strip", "fan"]);
+        assert_eq!(mesh.positions.len(), 12);
+        assert_near(mesh.positions[3], [1.0, 1.0, 5.0]);
+        // mirrored through x under the parent
+        assert_near(mesh.positions[5], [-1.0, 0.0, 5.0]);
+        // both translations add up
+        assert_near(mesh.positions[11], [11.0, 1.0, 5.0]);
+
+        let faces: Vec<([usize; 3], usize, usize)> =
+            mesh.faces.iter().map(|face| (face.positions, face.material, face.group)).collect();
+        assert_eq!(
+            faces,
+            [
+                ([0, 1, 2], 1, 0),
+                ([2, 1, 3], 1, 0),
+                // a strip turns every other triangle around, and the mirror flips both back
+                ([4, 6, 5], 2, 1),
+                ([6, 7, 5], 2, 1),
+                ([8, 9, 10], 3, 2),
+                ([8, 10, 11], 3, 2),
+            ]
+        );
+        // the triangles and the strip still face +z in world space, mirrored or not
+        for face in &mesh.faces[..4] {
+            let [a, b, c] = face.positions.map(|i| mesh.positions[i]);
+            assert!((b - a).cross(&(c - a)).z() > 0.0);
+        }
+    }
+
+    #[test]
+    fn reads_strided_normalized_uvs() {
+        let gltf = parse_text(&scene()).unwrap();
+        let mesh = &gltf.mesh;
+        assert_eq!(mesh.uvs, [(0.0, 1.0), (1.0, 1.0), (0.0, 0.0), (1.0, 0.0)]);
+        assert_eq!(mesh.faces[0].uvs, Some([0, 1, 2]));
+        assert_eq!(mesh.faces[2].uvs, None);
+    }
+
+    #[test]
+    fn converts_materials() {
+        let gltf = parse_text(&scene()).unwrap();
+        let materials = &gltf.mesh.materials;
+        // the default one comes first
+        assert_eq!(materials.len(), 4);
+        assert!(!materials[1].is_emissive() && !materials[1].is_specular());
+        assert!(materials[2].is_emissive());
+        assert!(materials[3].is_specular());
+    }
+
+    #[test]
+    fn places_cameras_and_lights() {
+        let gltf = parse_text(&scene()).unwrap();
+        assert_eq!(gltf.cameras.len(), 1);
+        let camera = gltf.cameras[0];
+        assert_near(camera.look_from, [10.0, 0.0, 15.0]);
+        assert_near(camera.look_at, [10.0, 0.0, 14.0]);
+        assert!((camera.vfov - 0.8f64.to_degrees()).abs() < 1e-9);
+        assert_eq!(camera.aspect_ratio, Some(1.5));
+        assert_eq!(gltf.lights.len(), 1);
+        assert!(gltf.lights[0].is_light());
+    }
+
+    #[test]
+    fn reads_glb() {
+        let text = SCENE.replace(r#", "uri": "data:application/octet-stream;base64,BUFFER""#, "");
+        let chunk = |kind: &[u8; 4], mut data: Vec<u8>, pad: u8| {
+            while !data.len().is_multiple_of(4) {
+                data.push(pad);
+            }
+            let mut chunk = (data.len() as u32).to_le_bytes().to_vec();
+            chunk.extend_from_slice(kind);
+            chunk.extend(data);
+            chunk
+        };
+        let mut body = chunk(b"JSON", text.into_bytes(), b' ');
+        body.extend(chunk(b"BIN\0", buffer(), 0));
+        let mut glb = b"glTF".to_vec();
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(12 + body.len() as u32).to_le_bytes());
+        glb.extend(body);
+        let default: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)));
+        let gltf = parse(&glb, Path::new(""), default.clone()).unwrap();
+        assert_eq!(gltf.mesh.faces.len(), 6);
+        assert_near(gltf.mesh.positions[3], [1.0, 1.0, 5.0]);
+
+        glb.truncate(glb.len() - 8);
+        assert!(parse(&glb, Path::new(""), default).is_err());
+    }
+
+    #[test]
+    fn rejects_huge_accessor_counts() {
+        // overflows counting the values, overflows working out the end, and fits in memory
+        // but not in the buffer
+        for (count, message) in [
+            ("9223372036854775807", "accessor is too big"),
+            ("2305843009213693952", "accessor is too big"),
+            ("1000", "runs past the end"),
+        ] {
+            let text = scene().replacen(r#""count": 4, "type": "VEC3""#, &format!(r#""count": {}, "type": "VEC3""#, count), 1);
+            let error = parse_text(&text).err().expect("a huge count should be rejected");
+            assert!(error.to_string().contains(message), "count {}: {}", count, error);
+        }
+        let text = scene().replacen(r#""count": 6, "type": "SCALAR""#, r#""count": 9223372036854775807, "type": "SCALAR""#, 1);
+        assert!(parse_text(&text).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_indices_and_cycles() {
+        let text = scene().replace(r#""children": [3]"#, r#""children": [0]"#);
+        let error = parse_text(&text).err().expect("a node hierarchy with a loop should be rejected");
+        assert!(error.to_string().contains("loops back"), "{}", error);
+        let text = scene().replace(r#""children": [3]"#, r#""children": [7]"#);
+        assert!(parse_text(&text).is_err());
+        let text = scene().replace(r#""indices": 1, "material": 0"#, r#""indices": 1, "material": 9"#);
+        assert!(parse_text(&text).is_err());
+        // the u16 indices read as u32s, which point way past the 4 vertices
+        let text = scene().replace(r#""componentType": 5123, "count": 6"#, r#""componentType": 5125, "count": 3"#);
+        assert!(parse_text(&text).is_err());
+    }
+}
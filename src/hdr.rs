@@ -0,0 +1,504 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+use crate::utils::netpbm_header;
+use crate::vec3::Vec3;
+
+// floating point image formats that keep the linear radiance the camera hands back, so
+// nothing above 1.0 gets clipped and it can be tonemapped or composited later. both can
+// be read back too, which is how environment maps get in
+
+// radiance rgbe (.hdr). a shared exponent byte plus three 8 bit mantissas per pixel,
+// written with the usual per-channel run length encoding
+pub fn write_to_hdr(filename: &str, image: &[Vec<Vec3>]) -> io::Result<()> {
+    let height = image.len();
+    let width = image.first().map_or(0, |row| row.len());
+
+    let mut file = BufWriter::new(File::create(filename)?);
+    write!(file, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+
+    let mut scanline = vec![[0u8; 4]; width];
+    for row in image {
+        for (rgbe, pixel) in scanline.iter_mut().zip(row) {
+            *rgbe = to_rgbe(pixel);
+        }
+        // run length encoding only works for widths the format can put in its header
+        if !(8..=0x7fff).contains(&width) {
+            for rgbe in &scanline {
+                file.write_all(rgbe)?;
+            }
+            continue;
+        }
+        file.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for channel in 0..4 {
+            let values: Vec<u8> = scanline.iter().map(|rgbe| rgbe[channel]).collect();
+            write_rle(&mut file, &values)?;
+        }
+    }
+    file.flush()
+}
+
+fn to_rgbe(pixel: &Vec3) -> [u8; 4] {
+    let r = finite_or_zero(pixel.x());
+    let g = finite_or_zero(pixel.y());
+    let b = finite_or_zero(pixel.z());
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    let mut m = v / 2f64.powi(e);
+    // log2 can land a hair off right at powers of two
+    if m >= 1.0 {
+        m /= 2.0;
+        e += 1;
+    } else if m < 0.5 {
+        m *= 2.0;
+        e -= 1;
+    }
+    if e > 127 {
+        return [255, 255, 255, 255];
+    }
+    if e < -128 {
+        return [0, 0, 0, 0];
+    }
+    let scale = m * 256.0 / v;
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+// negative radiance doesn't mean anything and nan or inf would poison the whole file
+fn finite_or_zero(value: f64) -> f64 {
+    if value.is_finite() {
+        value.max(0.0)
+    } else {
+        0.0
+    }
+}
+
+// runs of 3+ equal bytes become (128 + count, byte), everything else goes out as
+// (count, bytes...) literals. both max out at 127 bytes
+fn write_rle(out: &mut impl Write, values: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 3;
+    let mut position = 0;
+    while position < values.len() {
+        // find the next run long enough to be worth encoding
+        let mut run_start = position;
+        let mut run_length = 0;
+        while run_start < values.len() {
+            run_length = 1;
+            while run_length < 127
+                && run_start + run_length < values.len()
+                && values[run_start + run_length] == values[run_start]
+            {
+                run_length += 1;
+            }
+            if run_length >= MIN_RUN {
+                break;
+            }
+            run_start += run_length;
+        }
+        if run_length < MIN_RUN {
+            run_start = values.len();
+        }
+
+        // literals up to the run
+        while position < run_start {
+            let count = (run_start - position).min(127);
+            out.write_all(&[count as u8])?;
+            out.write_all(&values[position..position + count])?;
+            position += count;
+        }
+        if run_start < values.len() {
+            out.write_all(&[128 + run_length as u8, values[run_start]])?;
+            position = run_start + run_length;
+        }
+    }
+    Ok(())
+}
+
+fn invalid_hdr(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("not a usable hdr: {}", message))
+}
+
+// reads .hdr files in the usual top to bottom, left to right orientation (-Y h +X w, or
+// +Y with the rows flipped), with or without run length encoding. EXPOSURE lines are
+// ignored like most readers do
+pub fn read_hdr(filename: &str) -> io::Result<Vec<Vec<Vec3>>> {
+    let data = fs::read(filename)?;
+    let mut position = 0;
+    let next_line = |position: &mut usize| -> io::Result<String> {
+        let start = *position;
+        let end = data[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map(|offset| start + offset)
+            .ok_or_else(|| invalid_hdr("header ends early"))?;
+        *position = end + 1;
+        Ok(String::from_utf8_lossy(&data[start..end]).trim_end().to_string())
+    };
+
+    if !next_line(&mut position)?.starts_with("#?") {
+        return Err(invalid_hdr("missing the #? signature"));
+    }
+    loop {
+        let line = next_line(&mut position)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_hdr(&format!("unsupported format {}", format)));
+            }
+        }
+    }
+    let resolution = next_line(&mut position)?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    let (flip, height, width) = match fields.as_slice() {
+        ["-Y", height, "+X", width] => (false, *height, *width),
+        ["+Y", height, "+X", width] => (true, *height, *width),
+        _ => return Err(invalid_hdr(&format!("unsupported orientation \"{}\"", resolution))),
+    };
+    let number = |text: &str| text.parse::<usize>().map_err(|_| invalid_hdr("bad image size"));
+    let (width, height) = (number(width)?, number(height)?);
+    if width == 0 || height == 0 {
+        return Err(invalid_hdr("bad image size"));
+    }
+
+    // every scanline takes at least 4 bytes, so the file caps the height. the width isn't
+    // capped that way (repeats can cover a lot of pixels) so the rows are only allocated if
+    // there's room for them
+    if height > (data.len() - position) / 4 {
+        return Err(invalid_hdr("file is truncated"));
+    }
+    let too_big = |_| invalid_hdr("image is too big");
+    width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(std::mem::size_of::<Vec3>()))
+        .ok_or_else(|| invalid_hdr("image is too big"))?;
+    let mut scanline = Vec::new();
+    scanline.try_reserve_exact(width).map_err(too_big)?;
+    scanline.resize(width, [0u8; 4]);
+    let mut image: Vec<Vec<Vec3>> = Vec::new();
+    image.try_reserve_exact(height).map_err(too_big)?;
+    for _ in 0..height {
+        position = read_scanline(&data, position, &mut scanline)?;
+        let mut row = Vec::new();
+        row.try_reserve_exact(width).map_err(too_big)?;
+        row.extend(scanline.iter().map(from_rgbe));
+        image.push(row);
+    }
+    if flip {
+        image.reverse();
+    }
+    Ok(image)
+}
+
+// one scanline starting at `position`, gives back where the next one starts
+fn read_scanline(data: &[u8], mut position: usize, scanline: &mut [[u8; 4]]) -> io::Result<usize> {
+    let width = scanline.len();
+    let byte = |position: usize| data.get(position).copied().ok_or_else(|| invalid_hdr("file is truncated"));
+
+    let new_rle = (8..=0x7fff).contains(&width)
+        && data.len() >= position + 4
+        && data[position] == 2
+        && data[position + 1] == 2
+        && data[position + 2] & 0x80 == 0;
+    if new_rle {
+        if ((data[position + 2] as usize) << 8 | data[position + 3] as usize) != width {
+            return Err(invalid_hdr("scanline width doesn't match the image"));
+        }
+        position += 4;
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = byte(position)? as usize;
+                position += 1;
+                if count > 128 {
+                    let run = count - 128;
+                    let value = byte(position)?;
+                    position += 1;
+                    if x + run > width {
+                        return Err(invalid_hdr("run goes past the end of the scanline"));
+                    }
+                    for pixel in &mut scanline[x..x + run] {
+                        pixel[channel] = value;
+                    }
+                    x += run;
+                } else {
+                    if count == 0 || x + count > width {
+                        return Err(invalid_hdr("bad literal run"));
+                    }
+                    for pixel in &mut scanline[x..x + count] {
+                        pixel[channel] = byte(position)?;
+                        position += 1;
+                    }
+                    x += count;
+                }
+            }
+        }
+        return Ok(position);
+    }
+
+    // flat pixels, where (1, 1, 1, n) repeats the previous pixel n times. consecutive repeats
+    // stack up as the next 8 bits of the count
+    let mut x = 0;
+    let mut shift = 0;
+    while x < width {
+        if data.len() < position + 4 {
+            return Err(invalid_hdr("file is truncated"));
+        }
+        let rgbe = [data[position], data[position + 1], data[position + 2], data[position + 3]];
+        position += 4;
+        if rgbe[0] == 1 && rgbe[1] == 1 && rgbe[2] == 1 {
+            if x == 0 || shift > 24 {
+                return Err(invalid_hdr("bad repeat"));
+            }
+            let count = (rgbe[3] as usize) << shift;
+            if x + count > width {
+                return Err(invalid_hdr("repeat goes past the end of the scanline"));
+            }
+            let previous = scanline[x - 1];
+            for pixel in &mut scanline[x..x + count] {
+                *pixel = previous;
+            }
+            x += count;
+            shift += 8;
+        } else {
+            scanline[x] = rgbe;
+            x += 1;
+            shift = 0;
+        }
+    }
+    Ok(position)
+}
+
+fn from_rgbe(rgbe: &[u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::new(0.0, 0.0, 0.0);
+    }
+    // mantissas are floored on the way in, so take the middle of the bucket
+    let scale = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Vec3::new(
+        (rgbe[0] as f64 + 0.5) * scale,
+        (rgbe[1] as f64 + 0.5) * scale,
+        (rgbe[2] as f64 + 0.5) * scale,
+    )
+}
+
+// portable float map (.pfm). plain little endian f32 rgb, rows stored bottom to top
+pub fn write_to_pfm(filename: &str, image: &[Vec<Vec3>]) -> io::Result<()> {
+    let height = image.len();
+    let width = image.first().map_or(0, |row| row.len());
+
+    let mut file = BufWriter::new(File::create(filename)?);
+    // a negative scale means little endian
+    write!(file, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in image.iter().rev() {
+        for pixel in row {
+            for channel in [pixel.x(), pixel.y(), pixel.z()] {
+                file.write_all(&(channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+
+// reads colour (PF) and greyscale (Pf) float maps in either byte order
+pub fn read_pfm(filename: &str) -> io::Result<Vec<Vec<Vec3>>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not a usable pfm: {}", message));
+    let data = fs::read(filename)?;
+
+    let (header, mut position) = netpbm_header(&data, 4).map_err(|_| invalid("header ends early"))?;
+    let channels = match header[0].as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid("missing the PF or Pf signature")),
+    };
+    let number = |text: &str| text.parse::<usize>().map_err(|_| invalid("bad image size"));
+    let (width, height) = (number(&header[1])?, number(&header[2])?);
+    let scale: f64 = header[3].parse().map_err(|_| invalid("bad scale"))?;
+    if width == 0 || height == 0 || scale == 0.0 || !scale.is_finite() {
+        return Err(invalid("bad image size or scale"));
+    }
+    let little_endian = scale < 0.0;
+    // exactly one whitespace byte between the header and the samples
+    position += 1;
+
+    let expected = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(4 * channels))
+        .ok_or_else(|| invalid("image is too big"))?;
+    if data.len() < position + expected {
+        return Err(invalid("file is truncated"));
+    }
+    let samples = &data[position..position + expected];
+    let sample = |i: usize| -> f64 {
+        let bytes = [samples[4 * i], samples[4 * i + 1], samples[4 * i + 2], samples[4 * i + 3]];
+        let value = if little_endian { f32::from_le_bytes(bytes) } else { f32::from_be_bytes(bytes) };
+        value as f64
+    };
+
+    // stored bottom to top
+    Ok((0..height)
+        .rev()
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let i = channels * (y * width + x);
+                    if channels == 3 {
+                        Vec3::new(sample(i), sample(i + 1), sample(i + 2))
+                    } else {
+                        Vec3::new(sample(i), sample(i), sample(i))
+                    }
+                })
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("raytracer-{}-{}", std::process::id(), name))
+    }
+
+    // smooth ramps, a long flat stretch (runs), black and a couple of very bright pixels
+    fn test_image(width: usize, height: usize) -> Vec<Vec<Vec3>> {
+        (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| match x % 40 {
+                        0 => Vec3::new(0.0, 0.0, 0.0),
+                        1 => Vec3::new(5000.0, 20.0, 0.001),
+                        2..=20 => Vec3::new(0.25, 0.5, 0.75),
+                        _ => Vec3::new(x as f64 / width as f64, y as f64 / 7.0, 0.5 + 0.01 * (x * y) as f64),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // rgbe keeps 8 bits of mantissa for the brightest channel, the others share its exponent
+    fn assert_close(read: &[Vec<Vec3>], written: &[Vec<Vec3>]) {
+        assert_eq!(read.len(), written.len());
+        for (read_row, written_row) in read.iter().zip(written) {
+            assert_eq!(read_row.len(), written_row.len());
+            for (a, b) in read_row.iter().zip(written_row) {
+                let tolerance = b.x().max(b.y()).max(b.z()) / 128.0;
+                for channel in 0..3 {
+                    assert!((a[channel] - b[channel]).abs() <= tolerance, "wrote {:?}, read back {:?}", b, a);
+                }
+            }
+        }
+    }
+
+    fn round_trip_hdr(name: &str, image: &[Vec<Vec3>]) -> (Vec<Vec<Vec3>>, Vec<u8>) {
+        let path = temp_file(name);
+        let filename = path.to_str().unwrap();
+        write_to_hdr(filename, image).unwrap();
+        let bytes = fs::read(filename).unwrap();
+        let read = read_hdr(filename);
+        fs::remove_file(filename).unwrap();
+        (read.unwrap(), bytes)
+    }
+
+    #[test]
+    fn hdr_round_trips_run_length_encoded() {
+        for width in [8, 300] {
+            let image = test_image(width, 5);
+            let (read, bytes) = round_trip_hdr(&format!("rle-{}.hdr", width), &image);
+            assert_close(&read, &image);
+            // every scanline starts with the 2 2 marker and the width
+            let header_end = bytes.windows(2).position(|pair| pair == b"\n\n").unwrap();
+            let data = &bytes[header_end + 2..];
+            let data = &data[data.iter().position(|&byte| byte == b'\n').unwrap() + 1..];
+            assert_eq!(data[..4], [2, 2, (width >> 8) as u8, width as u8]);
+            if width == 300 {
+                assert!(data.len() < 4 * 300 * 5, "runs should make the file smaller");
+            }
+        }
+    }
+
+    #[test]
+    fn hdr_round_trips_flat() {
+        let image = test_image(5, 3);
+        let (read, bytes) = round_trip_hdr("flat.hdr", &image);
+        assert_close(&read, &image);
+        assert!(bytes.ends_with(&to_rgbe(&image[2][4])));
+        assert_eq!(to_rgbe(&image[0][0]), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn hdr_clamps_what_it_cant_store() {
+        assert_eq!(to_rgbe(&Vec3::new(f64::NAN, -1.0, f64::INFINITY)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(&Vec3::new(1e300, 0.0, 0.0)), [255, 255, 255, 255]);
+        // exactly a power of two is where log2 can come out a hair off
+        assert_eq!(to_rgbe(&Vec3::new(1.0, 0.5, 0.0)), [128, 64, 0, 129]);
+    }
+
+    fn read_bytes(name: &str, bytes: &[u8]) -> io::Result<Vec<Vec<Vec3>>> {
+        let path = temp_file(name);
+        let filename = path.to_str().unwrap();
+        fs::write(filename, bytes).unwrap();
+        let read = read_hdr(filename);
+        fs::remove_file(filename).unwrap();
+        read
+    }
+
+    #[test]
+    fn hdr_rejects_bad_headers() {
+        let header = "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n";
+        let cases = [
+            ("no blank line", "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n".to_string()),
+            ("no resolution", header.to_string()),
+            ("wider than memory", format!("{}-Y 1 +X 4000000000000000\n{}", header, "x".repeat(20))),
+            ("overflows", format!("{}-Y 18446744073709551615 +X 18446744073709551615\n", header)),
+            ("taller than the file", format!("{}-Y 4000000000 +X 8\n{}", header, "x".repeat(20))),
+            ("sideways", format!("{}+X 2 -Y 2\n", header)),
+            ("zero sized", format!("{}-Y 0 +X 5\n", header)),
+        ];
+        for (name, text) in cases {
+            assert!(read_bytes(&format!("bad-{}.hdr", name.replace(' ', "-")), text.as_bytes()).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn hdr_rejects_truncated_pixels() {
+        let image = test_image(300, 3);
+        let (_, bytes) = round_trip_hdr("whole.hdr", &image);
+        for cut in [1, 50, bytes.len() / 2] {
+            let cut = &bytes[..bytes.len() - cut];
+            assert!(read_bytes("cut.hdr", cut).is_err());
+        }
+        let (_, bytes) = round_trip_hdr("whole-flat.hdr", &test_image(5, 3));
+        assert!(read_bytes("cut-flat.hdr", &bytes[..bytes.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn pfm_round_trips_exactly() {
+        let image = test_image(45, 4);
+        let path = temp_file("round-trip.pfm");
+        let filename = path.to_str().unwrap();
+        write_to_pfm(filename, &image).unwrap();
+        let read = read_pfm(filename);
+        fs::remove_file(filename).unwrap();
+        let read = read.unwrap();
+        assert_eq!(read.len(), image.len());
+        for (read_row, written_row) in read.iter().zip(&image) {
+            for (a, b) in read_row.iter().zip(written_row) {
+                for channel in 0..3 {
+                    assert_eq!(a[channel], b[channel] as f32 as f64);
+                }
+            }
+        }
+    }
+}
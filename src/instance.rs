@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::mat4::Mat4;
+use crate::object::{HitRecord, Hittable};
+use crate::rand::Rand;
+use crate::ray3::Ray3;
+use crate::vec3::{Point3, Vec3};
+
+// some other object moved, turned and stretched by a transform, without touching (or copying)
+// the object itself. rays get taken into the object's own space and the hit brought back,
+// so one mesh in a Group can be placed thousands of times for the price of a matrix each
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    // object space to world space, and back
+    transform: Mat4,
+    inverse: Mat4,
+    normal_matrix: Mat4,
+    bounds: Aabb,
+}
+
+impl Instance {
+    // none if the transform flattens things (a 0 scale) and rays can't be taken back through it
+    pub fn new(object: Arc<dyn Hittable>, transform: Mat4) -> Option<Self> {
+        let inverse = transform.inverse()?;
+        let bounds = transform.transform_box(&object.bounding_box());
+        Some(Self { object, transform, inverse, normal_matrix: transform.normal_matrix(), bounds })
+    }
+
+    pub fn transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    // the ray in object space. Ray3 normalizes its direction, so a distance along the object
+    // space ray is `stretch` times the same distance along the world one
+    fn to_object(&self, ray: &Ray3) -> (Ray3, f64) {
+        let direction = self.inverse.transform_vector(&ray.direction());
+        (Ray3::new(self.inverse.transform_point(&ray.origin()), direction), direction.length())
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (local, stretch) = self.to_object(ray);
+        let hit = self.object.hit(&local, t_min * stretch, t_max * stretch)?;
+        let outward_normal = if hit.front_face { hit.normal } else { -hit.normal };
+        let normal = self.normal_matrix.transform_vector(&outward_normal).unit_vector();
+        Some(HitRecord::new(ray, hit.t / stretch, normal, (hit.u, hit.v), hit.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    // the object gives a density over its own directions, which the transform squeezes and
+    // spreads out. a linear map A takes a small patch of directions around d to one
+    // |det A| / |A d|^3 times the size, with A the inverse here since that's the way into
+    // object space
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let (local, stretch) = self.to_object(&Ray3::new(*origin, *direction));
+        let pdf = self.object.pdf_value(&local.origin(), &local.direction());
+        pdf * self.inverse.determinant().abs() / (stretch * stretch * stretch)
+    }
+
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        let local_origin = self.inverse.transform_point(origin);
+        self.transform.transform_vector(&self.object.random(&local_origin, randomizer))
+    }
+}
@@ -0,0 +1,444 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// small json parser. every value remembers the line and column it started at, so the
+// scene loader can point at the exact spot when something in a file is wrong
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+// for loaders that read json alongside binary data and report everything as io errors
+impl From<ParseError> for io::Error {
+    fn from(error: ParseError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // kept in file order, duplicate keys are rejected while parsing
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Json {
+    pub value: Value,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub fn parse(text: &str) -> Result<Json, ParseError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+        line: 1,
+        column: 1,
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+impl Json {
+    pub fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.value {
+            Value::Null => "null",
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Object(_) => "an object",
+        }
+    }
+
+    // looks up a key if this is an object, None for anything else
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match &self.value {
+            Value::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Result<f64, ParseError> {
+        match self.value {
+            Value::Number(n) => Ok(n),
+            _ => Err(self.error(format!("expected a number, found {}", self.type_name()))),
+        }
+    }
+
+    // non negative whole number
+    pub fn as_u64(&self) -> Result<u64, ParseError> {
+        let n = self.as_f64()?;
+        if n < 0.0 || n.fract() != 0.0 || n > u64::MAX as f64 {
+            return Err(self.error(format!("expected a non-negative integer, found {}", n)));
+        }
+        Ok(n as u64)
+    }
+
+    pub fn as_bool(&self) -> Result<bool, ParseError> {
+        match self.value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(self.error(format!("expected a boolean, found {}", self.type_name()))),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, ParseError> {
+        match &self.value {
+            Value::String(s) => Ok(s),
+            _ => Err(self.error(format!("expected a string, found {}", self.type_name()))),
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&[Json], ParseError> {
+        match &self.value {
+            Value::Array(items) => Ok(items),
+            _ => Err(self.error(format!("expected an array, found {}", self.type_name()))),
+        }
+    }
+
+    pub fn as_object(&self) -> Result<&[(String, Json)], ParseError> {
+        match &self.value {
+            Value::Object(entries) => Ok(entries),
+            _ => Err(self.error(format!("expected an object, found {}", self.type_name()))),
+        }
+    }
+}
+
+// deep enough for anything sane, shallow enough that a hostile file can't blow the stack
+const MAX_DEPTH: usize = 256;
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}', found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}', found end of file", expected))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == ' ' || c == '\t' || c == '\n' || c == '\r' {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_value(&mut self, depth: usize) -> Result<Json, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        let line = self.line;
+        let column = self.column;
+        let value = match self.peek() {
+            None => return Err(self.error("unexpected end of file")),
+            Some('{') => self.parse_object(depth)?,
+            Some('[') => self.parse_array(depth)?,
+            Some('"') => Value::String(self.parse_string()?),
+            Some('t') => self.parse_literal("true", Value::Bool(true))?,
+            Some('f') => self.parse_literal("false", Value::Bool(false))?,
+            Some('n') => self.parse_literal("null", Value::Null)?,
+            Some(c) if c == '-' || c.is_ascii_digit() => Value::Number(self.parse_number()?),
+            Some(c) => return Err(self.error(format!("unexpected character '{}'", c))),
+        };
+        Ok(Json { value, line, column })
+    }
+
+    fn parse_literal(&mut self, word: &str, value: Value) -> Result<Value, ParseError> {
+        let start = self.error(format!("expected '{}'", word));
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return Err(start);
+            }
+            self.bump();
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<f64, ParseError> {
+        let start = self.error("invalid number");
+        let mut text = String::new();
+        if self.peek() == Some('-') {
+            text.push('-');
+            self.bump();
+        }
+        // json doesn't allow leading zeros like 007
+        match self.peek() {
+            Some('0') => {
+                text.push('0');
+                self.bump();
+            }
+            Some(c) if c.is_ascii_digit() => self.take_digits(&mut text),
+            _ => return Err(start),
+        }
+        if self.peek() == Some('.') {
+            text.push('.');
+            self.bump();
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("expected a digit after the decimal point"));
+            }
+            self.take_digits(&mut text);
+        }
+        if let Some(e @ ('e' | 'E')) = self.peek() {
+            text.push(e);
+            self.bump();
+            if let Some(sign @ ('+' | '-')) = self.peek() {
+                text.push(sign);
+                self.bump();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("expected a digit in the exponent"));
+            }
+            self.take_digits(&mut text);
+        }
+        text.parse::<f64>().map_err(|_| start)
+    }
+
+    fn take_digits(&mut self, text: &mut String) {
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            text.push(c);
+            self.bump();
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .peek()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("expected four hex digits after \\u"))?;
+            self.bump();
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let escape_start = self.error("invalid escape");
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code = self.parse_hex4()?;
+                            // utf-16 surrogate pair, the low half has to follow right away
+                            if (0xd800..0xdc00).contains(&code) {
+                                if self.bump() != Some('\\') || self.bump() != Some('u') {
+                                    return Err(escape_start);
+                                }
+                                let low = self.parse_hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(escape_start);
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(code).ok_or(escape_start)?
+                        }
+                        _ => return Err(escape_start),
+                    };
+                    s.push(c);
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            items.push(self.parse_value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {
+                    self.bump();
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Value, ParseError> {
+        self.expect('{')?;
+        let mut entries: Vec<(String, Json)> = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key_error = self.error("duplicate key");
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.parse_string()?;
+            if entries.iter().any(|(k, _)| *k == key) {
+                return Err(ParseError {
+                    message: format!("duplicate key \"{}\"", key),
+                    ..key_error
+                });
+            }
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.parse_value(depth + 1)?;
+            entries.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some('}') => {
+                    self.bump();
+                    return Ok(Value::Object(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(text: &str) -> (usize, usize, String) {
+        let error = parse(text).expect_err("should not parse");
+        (error.line, error.column, error.message)
+    }
+
+    #[test]
+    fn reports_where_a_comma_is_missing() {
+        let (line, column, message) = error_at("{\n  \"a\": 1\n  \"b\": 2\n}");
+        assert_eq!((line, column), (3, 3));
+        assert_eq!(message, "expected ',' or '}' in object");
+        assert_eq!(error_at("[1,\n 2\n 3]"), (3, 2, "expected ',' or ']' in array".to_string()));
+    }
+
+    #[test]
+    fn reports_bad_escapes_at_the_backslash() {
+        assert_eq!(error_at("{\"name\": \"a\\qb\"}"), (1, 12, "invalid escape".to_string()));
+        // the low half of a surrogate pair is missing
+        assert_eq!(error_at("[\n\"\\ud83d x\"]"), (2, 2, "invalid escape".to_string()));
+    }
+
+    #[test]
+    fn reports_trailing_garbage() {
+        assert_eq!(error_at("{\n  \"a\": 1\n} x"), (3, 3, "unexpected trailing characters".to_string()));
+    }
+
+    // columns count characters, not bytes
+    #[test]
+    fn columns_count_characters() {
+        assert_eq!(error_at("[\"é\", tru]"), (1, 7, "expected 'true'".to_string()));
+    }
+
+    #[test]
+    fn values_remember_where_they_started() {
+        let json = parse("{\n  \"size\": [1, 2],\n  \"name\":\t\"x\"\n}").unwrap();
+        assert_eq!((json.line, json.column), (1, 1));
+        let size = json.get("size").unwrap();
+        assert_eq!((size.line, size.column), (2, 11));
+        assert_eq!((size.as_array().unwrap()[1].line, size.as_array().unwrap()[1].column), (2, 15));
+        let error = json.get("name").unwrap().as_f64().unwrap_err();
+        assert_eq!(error.to_string(), "3:11: expected a number, found a string");
+        let duplicate = parse("{\"a\": 1,\n \"a\": 2}").unwrap_err();
+        assert_eq!((duplicate.line, duplicate.column), (2, 2));
+    }
+}
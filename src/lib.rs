@@ -4,3 +4,23 @@ pub mod ray3;
 pub mod camera;
 pub mod object;
 pub mod rand;
+pub mod scene;
+pub mod aabb;
+pub mod bvh;
+pub mod material;
+pub mod json;
+pub mod scene_file;
+pub mod deflate;
+pub mod png;
+pub mod hdr;
+pub mod exr;
+pub mod tonemap;
+pub mod environment;
+pub mod distribution;
+pub mod mesh;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+pub mod gltf;
+pub mod mat4;
+pub mod instance;
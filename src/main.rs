@@ -1,17 +1,256 @@
 use raytracer::{
     camera::Camera,
-    ray3::Ray3,
-    utils,
-    vec3::{Point3, Vec3},
-    rand::Rand
+    material::Lambertian,
+    object::{Hittable, Plane, Sphere},
+    scene::Scene,
+    scene_file,
+    tonemap::{PostProcess, ToneMap},
+    utils::{self, ImageFormat},
+    vec3::{Color3, Point3, Vec3},
 };
-use std::io;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::Arc;
 
-fn main() -> io::Result<()> {
-    let view_camera = Camera::new(16.0 / 9.0, 400, 0.5, 5.0, Point3::new(0.0, 0.0, 0.0));
-    let image = view_camera.smile();
+const USAGE: &str = "\
+usage: raytracer [OPTIONS] [SCENE]
 
-    utils::write_to_ppm("output.ppm", &image)?;
+renders SCENE (a json scene file, or a .gltf or .glb) or, without one, the built in sphere grid
 
-    Ok(())
+options:
+  -o, --output <PATH>        where to write the image [default: output.ppm]
+  -f, --format <FORMAT>      image format, guessed from the output extension if not given
+                             (ppm, png, png16 for 16 bits per channel, and hdr, pfm,
+                             exr, exr-uncompressed for unclipped linear radiance)
+  -W, --width <PIXELS>       image width, keeps the scene's aspect ratio unless --height is given
+  -H, --height <PIXELS>      image height, keeps the scene's aspect ratio unless --width is given
+  -s, --samples <N>          samples per pixel
+  -d, --max-depth <N>        maximum number of bounces per path
+      --seed <N>             seed for the random numbers, same seed gives the same image
+  -t, --threads <N>          render threads, 0 uses every core
+      --tonemap <NAME>       tone mapping for ppm and png output (clamp, reinhard,
+                             extended-reinhard, aces, agx) [default: clamp]
+      --exposure <STOPS>     brighten (or darken, if negative) before tone mapping
+      --white-point <W>      radiance that maps to white with extended-reinhard [default: 4]
+  -h, --help                 print this and exit
+
+exit codes: 0 on success, 1 if rendering or writing failed, 2 for bad arguments";
+
+// what we got on the command line. anything left as None falls back to the scene
+#[derive(Default)]
+struct Args {
+    scene: Option<String>,
+    output: Option<String>,
+    format: Option<ImageFormat>,
+    width: Option<u32>,
+    height: Option<u32>,
+    samples_per_pixel: Option<u32>,
+    max_depth: Option<u32>,
+    seed: Option<u64>,
+    threads: Option<usize>,
+    tone_map: Option<ToneMap>,
+    exposure: Option<f64>,
+    white_point: Option<f64>,
+    help: bool,
+}
+
+fn parse_number<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+fn parse_args(mut raw: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut args = Args::default();
+    while let Some(arg) = raw.next() {
+        // allow --flag=value as well as --flag value
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        if flag == "-h" || flag == "--help" {
+            args.help = true;
+            continue;
+        }
+        if !flag.starts_with('-') || flag == "-" {
+            if args.scene.is_some() {
+                return Err(format!("unexpected argument '{}', only one scene can be rendered", arg));
+            }
+            args.scene = Some(arg);
+            continue;
+        }
+
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| raw.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match flag.as_str() {
+            "-o" | "--output" => args.output = Some(value()?),
+            "-f" | "--format" => {
+                let name = value()?;
+                args.format = Some(ImageFormat::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown format '{}' (expected one of: {})",
+                        name,
+                        ImageFormat::names().join(", ")
+                    )
+                })?);
+            }
+            "-W" | "--width" => args.width = Some(parse_number(&flag, &value()?)?),
+            "-H" | "--height" => args.height = Some(parse_number(&flag, &value()?)?),
+            "-s" | "--samples" => args.samples_per_pixel = Some(parse_number(&flag, &value()?)?),
+            "-d" | "--max-depth" => args.max_depth = Some(parse_number(&flag, &value()?)?),
+            "--seed" => args.seed = Some(parse_number(&flag, &value()?)?),
+            "-t" | "--threads" => args.threads = Some(parse_number(&flag, &value()?)?),
+            "--tonemap" => {
+                let name = value()?;
+                args.tone_map = Some(ToneMap::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown tone mapping '{}' (expected one of: {})",
+                        name,
+                        ToneMap::names().join(", ")
+                    )
+                })?);
+            }
+            "--exposure" => args.exposure = Some(parse_number(&flag, &value()?)?),
+            "--white-point" => args.white_point = Some(parse_number(&flag, &value()?)?),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+
+    if args.width == Some(0) || args.height == Some(0) {
+        return Err("image size has to be at least 1x1".to_string());
+    }
+    if args.samples_per_pixel == Some(0) {
+        return Err("--samples has to be at least 1".to_string());
+    }
+    if args.exposure.is_some_and(|exposure| !exposure.is_finite()) {
+        return Err("--exposure has to be a finite number".to_string());
+    }
+    if let Some(white_point) = args.white_point {
+        if !(white_point > 0.0 && white_point.is_finite()) {
+            return Err("--white-point has to be greater than 0".to_string());
+        }
+        if args.tone_map.is_some_and(|tone_map| !matches!(tone_map, ToneMap::ExtendedReinhard { .. })) {
+            return Err("--white-point only applies to --tonemap extended-reinhard".to_string());
+        }
+    }
+    Ok(args)
+}
+
+fn make_spheres() -> Scene {
+    let mut objects: Vec<Box<dyn Hittable>> = vec![
+        // Ground plane (yellow Lambertian)
+        Box::new(Plane::new(
+            Point3::new(0.0, -5.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Color3::new(0.8, 0.8, 0.0))),
+        )),
+    ];
+
+    for j in -10..10 {
+        for i in -10..10 {
+            objects.push(
+                Box::new(Sphere::new(
+                    Point3::new(j as f64, -4.0, i as f64),
+                    0.5,
+                    Arc::new(Lambertian::new(Color3::new((j + 10) as f64 / 20.0, (i +10) as f64 / 20.0, 1.0))),
+                ))
+            );
+        }
+    }
+    Scene::new(objects)
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let output = args.output.unwrap_or_else(|| "output.ppm".to_string());
+    let format = match args.format.or_else(|| ImageFormat::from_path(&output)) {
+        Some(format) => format,
+        None => return Err(format!("can't tell the image format of '{}', pass --format", output)),
+    };
+
+    // render a scene file if we got one, otherwise the built in sphere grid
+    let (mut view_camera, scene, mut post_process) = match &args.scene {
+        Some(path) => {
+            let scene_file = scene_file::load(path).map_err(|e| e.to_string())?;
+            (scene_file.camera, scene_file.scene, scene_file.post_process)
+        }
+        None => (
+            // looking down -z with a 5 tall viewport half a unit away, about 157 degrees
+            Camera::new(
+                16.0 / 9.0,
+                400,
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                2.0 * (2.5f64 / 0.5).atan().to_degrees(),
+            ),
+            make_spheres(),
+            PostProcess::default(),
+        ),
+    };
+
+    let aspect_ratio = view_camera.aspect_ratio();
+    let (width, height) = match (args.width, args.height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, (width as f64 / aspect_ratio) as u32),
+        (None, Some(height)) => ((height as f64 * aspect_ratio) as u32, height),
+        (None, None) => (view_camera.image_width(), view_camera.image_height()),
+    };
+    view_camera = view_camera.with_image_size(width, height);
+    if let Some(samples_per_pixel) = args.samples_per_pixel {
+        view_camera = view_camera.with_samples_per_pixel(samples_per_pixel);
+    }
+    if let Some(max_depth) = args.max_depth {
+        view_camera = view_camera.with_max_depth(max_depth);
+    }
+    if let Some(seed) = args.seed {
+        view_camera = view_camera.with_seed(seed);
+    }
+    if let Some(threads) = args.threads {
+        view_camera = view_camera.with_threads(threads);
+    }
+
+    if let Some(tone_map) = args.tone_map {
+        post_process.tone_map = tone_map;
+    }
+    if let Some(exposure) = args.exposure {
+        post_process.exposure = exposure;
+    }
+    // only tunes an extended-reinhard that's already in effect, from --tonemap or the scene,
+    // rather than quietly swapping out whatever curve the scene asked for
+    if let Some(white_point) = args.white_point {
+        let ToneMap::ExtendedReinhard { white } = &mut post_process.tone_map else {
+            return Err("--white-point only applies to the extended-reinhard tonemap, pass --tonemap extended-reinhard".to_string());
+        };
+        *white = white_point;
+    }
+
+    let image = view_camera.with_progress(true).smile(&scene);
+
+    utils::write_image(&output, &image, format, &post_process).map_err(|e| format!("{}: {}", output, e))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("raytracer: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("raytracer: {}", message);
+            ExitCode::from(1)
+        }
+    }
 }
@@ -0,0 +1,216 @@
+use std::ops::Mul;
+
+use crate::aabb::Aabb;
+use crate::vec3::{Point3, Vec3};
+
+// 4x4 matrix for moving things around, stored row by row and applied to column vectors, so
+// a * b does b first and then a. only affine transforms are ever built here, so the bottom
+// row is taken to be 0 0 0 1 when transforming and nothing gets divided by w
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {
+    rows: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        rows: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]],
+    };
+
+    pub fn from_rows(rows: [[f64; 4]; 4]) -> Self {
+        Self { rows }
+    }
+
+    // for formats like gltf that write their matrices out column by column
+    pub fn from_columns(columns: [[f64; 4]; 4]) -> Self {
+        Self::from_rows(columns).transpose()
+    }
+
+    pub fn rows(&self) -> [[f64; 4]; 4] {
+        self.rows
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        let mut matrix = Self::IDENTITY;
+        for axis in 0..3 {
+            matrix.rows[axis][3] = offset[axis];
+        }
+        matrix
+    }
+
+    pub fn scaling(factors: Vec3) -> Self {
+        let mut matrix = Self::IDENTITY;
+        for axis in 0..3 {
+            matrix.rows[axis][axis] = factors[axis];
+        }
+        matrix
+    }
+
+    // counterclockwise by `degrees` looking down `axis` towards the origin, right hand rule.
+    // the axis doesn't have to be unit length
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let (x, y, z) = {
+            let axis = axis.unit_vector();
+            (axis.x(), axis.y(), axis.z())
+        };
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos;
+        Self::from_rows([
+            [cos + x * x * k, x * y * k - z * sin, x * z * k + y * sin, 0.0],
+            [y * x * k + z * sin, cos + y * y * k, y * z * k - x * sin, 0.0],
+            [z * x * k - y * sin, z * y * k + x * sin, cos + z * z * k, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // rotation from a quaternion (x, y, z, w), normalized first in case it was written out
+    // with too few digits. all zeros is taken as no rotation
+    pub fn from_quaternion(x: f64, y: f64, z: f64, w: f64) -> Self {
+        let length = (x * x + y * y + z * z + w * w).sqrt();
+        if length == 0.0 {
+            return Self::IDENTITY;
+        }
+        let (x, y, z, w) = (x / length, y / length, z / length, w / length);
+        Self::from_rows([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w), 0.0],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w), 0.0],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut transposed = [[0.0; 4]; 4];
+        for (row, transposed_row) in transposed.iter_mut().enumerate() {
+            for (column, value) in transposed_row.iter_mut().enumerate() {
+                *value = self.rows[column][row];
+            }
+        }
+        Self::from_rows(transposed)
+    }
+
+    // of the upper left 3x3, which for an affine transform is the whole thing's. negative
+    // means it mirrors
+    pub fn determinant(&self) -> f64 {
+        let m = &self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // none if the matrix squashes space flat (a 0 scale) and can't be undone. this is the
+    // full 4x4 inverse, built out of the 2x2 determinants of the top and bottom row pairs
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.rows;
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let determinant = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let inverse = [
+            [
+                m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3,
+                -m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3,
+                m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3,
+                -m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3,
+            ],
+            [
+                -m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1,
+                m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1,
+                -m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1,
+                m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1,
+            ],
+            [
+                m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0,
+                -m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0,
+                m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0,
+                -m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0,
+            ],
+            [
+                -m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0,
+                m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0,
+                -m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0,
+                m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0,
+            ],
+        ];
+        Some(Self::from_rows(inverse.map(|row| row.map(|value| value / determinant))))
+    }
+
+    // what normals go through: the inverse transpose of the upper left 3x3. that's its
+    // cofactor matrix over the determinant, and since normals get normalized afterwards only
+    // the sign of the determinant matters. so unlike inverse this always works, even on a
+    // transform that flattens things
+    pub fn normal_matrix(&self) -> Self {
+        let m = &self.rows;
+        let sign = self.determinant().signum();
+        let mut cofactors = Self::IDENTITY;
+        for row in 0..3 {
+            for column in 0..3 {
+                let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+                let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+                cofactors.rows[row][column] = sign * (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]);
+            }
+        }
+        cofactors
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::new(self.rows[0][3], self.rows[1][3], self.rows[2][3])
+    }
+
+    // directions and offsets, which don't move with the translation
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.rows;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    // the box around the transformed box: the middle moves like a point, and each axis of
+    // the new half size is how far the old half sizes reach along it. boxes that go on
+    // forever (or are empty) come back going on forever
+    pub fn transform_box(&self, bounds: &Aabb) -> Aabb {
+        if !bounds.is_finite() {
+            let infinity = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+            return Aabb::new(-infinity, infinity);
+        }
+        let center = self.transform_point(&bounds.centroid());
+        let half = 0.5 * bounds.extent();
+        let mut reach = Vec3::new(0.0, 0.0, 0.0);
+        for row in 0..3 {
+            reach[row] = (0..3).map(|column| self.rows[row][column].abs() * half[column]).sum();
+        }
+        Aabb::new(center - reach, center + reach)
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut product = [[0.0; 4]; 4];
+        for (row, product_row) in product.iter_mut().enumerate() {
+            for (column, value) in product_row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.rows[row][k] * other.rows[k][column]).sum();
+            }
+        }
+        Mat4::from_rows(product)
+    }
+}
@@ -0,0 +1,166 @@
+use std::f64::consts::PI;
+
+use crate::object::HitRecord;
+use crate::rand::Rand;
+use crate::ray3::Ray3;
+use crate::vec3::*;
+
+// how light bounces off a surface. returns the attenuation and the scattered ray, or None
+// if the ray got absorbed. shapes hold these behind an Arc so one material can be shared
+// by as many objects as we like
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray_in: &Ray3, hit: &HitRecord, randomizer: &mut Rand) -> Option<(Color3, Ray3)>;
+
+    // light given off at the hit point towards where the ray came from. most things don't glow
+    fn emitted(&self, _ray_in: &Ray3, _hit: &HitRecord) -> Color3 {
+        Color3::new(0.0, 0.0, 0.0)
+    }
+
+    // true for lights, the scene keeps track of those so it can aim rays at them
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // the rest is for light sampling, which only works for materials that can say how much
+    // light they send from any given direction back along the ray. the ones that can't
+    // (mirrors, glass, anything else that only scatters in a few directions) stay specular
+    // and just get whatever their scattered rays happen to find
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    // brdf times the cosine at the surface for light arriving from `direction`
+    fn eval(&self, _ray_in: &Ray3, _hit: &HitRecord, _direction: &Vec3) -> Color3 {
+        Color3::new(0.0, 0.0, 0.0)
+    }
+
+    // solid angle density of scatter picking `direction`
+    fn pdf(&self, _ray_in: &Ray3, _hit: &HitRecord, _direction: &Vec3) -> f64 {
+        0.0
+    }
+}
+
+pub struct Lambertian {
+    pub albedo: Color3, // this is a color
+}
+
+impl Lambertian {
+    pub fn new(albedo: Color3) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(&self, _ray_in: &Ray3, hit: &HitRecord, randomizer: &mut Rand) -> Option<(Color3, Ray3)> {
+        let mut scatter_direction_vector = hit.normal + Vec3::random_unit_vector(randomizer);
+        if scatter_direction_vector.near_zero() {
+            scatter_direction_vector = hit.normal;
+        }
+        Some((self.albedo, Ray3::new(hit.point, scatter_direction_vector)))
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, _ray_in: &Ray3, hit: &HitRecord, direction: &Vec3) -> Color3 {
+        let cosine = hit.normal.dot(&direction.unit_vector());
+        if cosine <= 0.0 {
+            return Color3::new(0.0, 0.0, 0.0);
+        }
+        self.albedo * (cosine / PI)
+    }
+
+    // normal + a random unit vector comes out cosine distributed
+    fn pdf(&self, _ray_in: &Ray3, hit: &HitRecord, direction: &Vec3) -> f64 {
+        (hit.normal.dot(&direction.unit_vector()) / PI).max(0.0)
+    }
+}
+
+pub struct Metal {
+    pub albedo: Color3, // this is a color
+    pub fuzz: f64, // how much to fuzz the reflections
+}
+
+impl Metal {
+    pub fn new(albedo: Color3, fuzz: f64) -> Self {
+        Self { albedo, fuzz }
+    }
+}
+
+impl Material for Metal {
+    fn scatter(&self, ray_in: &Ray3, hit: &HitRecord, randomizer: &mut Rand) -> Option<(Color3, Ray3)> {
+        let reflected = reflect(&ray_in.direction(), &hit.normal).unit_vector() + (self.fuzz * Vec3::random_unit_vector(randomizer));
+        // fuzzed below the surface, the surface eats it
+        if reflected.dot(&hit.normal) <= 0.0 {
+            return None;
+        }
+        Some((self.albedo, Ray3::new(hit.point, reflected)))
+    }
+}
+
+// glass, water, diamond, anything that refracts
+pub struct Dielectric {
+    pub albedo: Color3,
+    pub refraction_index: f64,
+}
+
+impl Dielectric {
+    pub fn new(albedo: Color3, refraction_index: f64) -> Self {
+        Self { albedo, refraction_index }
+    }
+
+    // schlick's approximation
+    fn reflectance(&self, cosine: f64, refraction_index: f64) -> f64 {
+        let mut r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
+        r0 = r0 * r0;
+        r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(&self, ray_in: &Ray3, hit: &HitRecord, randomizer: &mut Rand) -> Option<(Color3, Ray3)> {
+        let ri = if hit.front_face { 1.0 / self.refraction_index } else { self.refraction_index };
+
+        let unit_direction = ray_in.direction(); // should already be a unit vec
+        let cos_theta = f64::min((-unit_direction).dot(&hit.normal), 1.0);
+        let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+        let cannot_refract = ri * sin_theta > 1.0;
+        let direction = if cannot_refract || self.reflectance(cos_theta, ri) > randomizer.next_f64() {
+            reflect(&unit_direction, &hit.normal)
+        } else {
+            refract(&unit_direction, &hit.normal, ri)
+        };
+        Some((self.albedo, Ray3::new(hit.point, direction)))
+    }
+}
+
+// glows with the same radiance in every direction off its front face and doesn't reflect
+// anything. put it on some geometry and you have an area light
+pub struct DiffuseLight {
+    pub emit: Color3, // radiance, can go well above 1
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color3) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray3, _hit: &HitRecord, _randomizer: &mut Rand) -> Option<(Color3, Ray3)> {
+        None
+    }
+
+    fn emitted(&self, _ray_in: &Ray3, hit: &HitRecord) -> Color3 {
+        if hit.front_face {
+            self.emit
+        } else {
+            Color3::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
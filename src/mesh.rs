@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::material::Material;
+use crate::object::{
+    intersect_triangle, triangle_bounding_box, triangle_hit_record, triangle_pdf_value, triangle_random, HitRecord,
+    Hittable,
+};
+use crate::rand::Rand;
+use crate::ray3::Ray3;
+use crate::vec3::{Point3, Vec3};
+
+// a triangle mesh with shared vertices, what the model loaders hand back. positions,
+// normals and uvs are separate pools (obj indexes them separately) and every face picks
+// from each of them
+#[derive(Default)]
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<Face>,
+    pub materials: Vec<Arc<dyn Material>>,
+    // names of the groups (or objects) faces can belong to
+    pub groups: Vec<String>,
+}
+
+// one triangle, counter clockwise seen from the front. everything is an index into the
+// pools of the mesh
+#[derive(Clone, Copy, Debug)]
+pub struct Face {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub material: usize,
+    pub group: usize,
+}
+
+impl Mesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        self.positions.iter().fold(Aabb::empty(), |bounds, position| bounds.grow(position))
+    }
+
+    fn vertices(&self, face: &Face) -> [Point3; 3] {
+        face.positions.map(|i| self.positions[i])
+    }
+
+    // same material on every face, whatever the file said
+    pub fn set_material(&mut self, material: Arc<dyn Material>) {
+        self.materials = vec![material];
+        for face in self.faces.iter_mut() {
+            face.material = 0;
+        }
+    }
+
+    // drops every face that isn't in one of the named groups
+    pub fn retain_groups(&mut self, names: &[&str]) {
+        let keep: Vec<bool> = self.groups.iter().map(|group| names.contains(&group.as_str())).collect();
+        self.faces.retain(|face| keep[face.group]);
+    }
+
+    // one hittable per face, all sharing this mesh. they go into the scene like any other
+    // object, so the bvh ends up over the individual triangles
+    pub fn into_hittables(self) -> Vec<Box<dyn Hittable>> {
+        let mesh = Arc::new(self);
+        (0..mesh.faces.len())
+            .map(|face| Box::new(MeshTriangle { mesh: mesh.clone(), face }) as Box<dyn Hittable>)
+            .collect()
+    }
+}
+
+pub struct MeshTriangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+}
+
+impl MeshTriangle {
+    fn face(&self) -> &Face {
+        &self.mesh.faces[self.face]
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let face = self.face();
+        let vertices = self.mesh.vertices(face);
+        let hit = intersect_triangle(ray, &vertices, t_min, t_max)?;
+        let normals = face.normals.map(|indices| indices.map(|i| self.mesh.normals[i]));
+        let uvs = face.uvs.map(|indices| indices.map(|i| self.mesh.uvs[i]));
+        Some(triangle_hit_record(
+            ray,
+            &vertices,
+            normals.as_ref(),
+            uvs.as_ref(),
+            hit,
+            self.mesh.materials[face.material].as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        triangle_bounding_box(&self.mesh.vertices(self.face()))
+    }
+
+    fn is_light(&self) -> bool {
+        self.mesh.materials[self.face().material].is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        triangle_pdf_value(&self.mesh.vertices(self.face()), origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        triangle_random(&self.mesh.vertices(self.face()), origin, randomizer)
+    }
+}
+
+// splits a simple polygon (convex or not) into triangles by ear clipping, in the plane the
+// polygon mostly lies in. gives back triples of indices into `points`, wound the same way as
+// the polygon. falls back to a fan if the polygon is too twisted to clip
+pub fn triangulate(points: &[Point3]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    let fan = || (1..n - 1).map(|i| [0, i, i + 1]).collect::<Vec<_>>();
+    if n == 3 {
+        return fan();
+    }
+
+    // newell's method for the normal, then drop its largest axis to get 2d points
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    for i in 0..n {
+        let current = points[i];
+        let next = points[(i + 1) % n];
+        normal[0] += (current.y() - next.y()) * (current.z() + next.z());
+        normal[1] += (current.z() - next.z()) * (current.x() + next.x());
+        normal[2] += (current.x() - next.x()) * (current.y() + next.y());
+    }
+    let axis = if normal.x().abs() > normal.y().abs() && normal.x().abs() > normal.z().abs() {
+        0
+    } else if normal.y().abs() > normal.z().abs() {
+        1
+    } else {
+        2
+    };
+    if normal[axis] == 0.0 {
+        return fan();
+    }
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+    // flip so the polygon always runs counter clockwise in 2d
+    let sign = normal[axis].signum();
+    let flat: Vec<(f64, f64)> = points.iter().map(|p| (p[a], sign * p[b])).collect();
+    let cross = |o: (f64, f64), p: (f64, f64), q: (f64, f64)| (p.0 - o.0) * (q.1 - o.1) - (p.1 - o.1) * (q.0 - o.0);
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let (previous, current, next) = (remaining[(i + m - 1) % m], remaining[i], remaining[(i + 1) % m]);
+            let (p, c, q) = (flat[previous], flat[current], flat[next]);
+            // has to be a convex corner with nothing else poking into it
+            cross(p, c, q) > 0.0
+                && remaining.iter().all(|&other| {
+                    other == previous
+                        || other == current
+                        || other == next
+                        || cross(p, c, flat[other]) < 0.0
+                        || cross(c, q, flat[other]) < 0.0
+                        || cross(q, p, flat[other]) < 0.0
+                })
+        });
+        let Some(i) = ear else {
+            return fan();
+        };
+        triangles.push([remaining[(i + m - 1) % m], remaining[i], remaining[(i + 1) % m]]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
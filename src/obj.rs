@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::{triangulate, Face, Mesh};
+use crate::vec3::{Color3, Point3};
+
+// wavefront .obj loader. reads positions (v), texture coordinates (vt), normals (vn), faces
+// (f, any number of corners, triangulated on the way in), groups and objects (g, o) and
+// materials from .mtl libraries (mtllib, usemtl). everything else (smoothing groups, lines,
+// curves, ...) is skipped
+//
+// mtl materials turn into ours like this:
+//   Ke above zero                            -> DiffuseLight(Ke)
+//   illum 4, 6, 7 or 9, or d / Tr see-through -> Dielectric(Tf, Ni)
+//   illum 3, or Pm >= 0.5                    -> Metal(Ks or Kd, fuzz from Pr or Ns)
+//   anything else                            -> Lambertian(Kd)
+
+// faces without a usemtl get `default_material`
+pub fn load(path: &str, default_material: Arc<dyn Material>) -> io::Result<Mesh> {
+    let text = fs::read_to_string(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    parse(&text, directory, default_material)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}:{}", path, e)))
+}
+
+// mtllib paths are relative to `directory`. errors start with the line number
+pub fn parse(text: &str, directory: &Path, default_material: Arc<dyn Material>) -> io::Result<Mesh> {
+    let mut mesh = Mesh::new();
+    mesh.materials.push(default_material);
+    mesh.groups.push("default".to_string());
+    let mut library: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    // which mesh material each usemtl name ended up as
+    let mut material_indices: HashMap<String, usize> = HashMap::new();
+    let mut material = 0;
+    let mut group = 0;
+
+    for (line_number, line) in logical_lines(text) {
+        let error = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", line_number, message));
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let Some(keyword) = fields.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = fields.collect();
+        match keyword {
+            "v" => mesh.positions.push(numbers(&rest, 3, 3).map_err(error)?.into()),
+            "vn" => mesh.normals.push(numbers(&rest, 3, 3).map_err(error)?.into()),
+            "vt" => {
+                let uv = numbers(&rest, 1, 2).map_err(error)?;
+                mesh.uvs.push((uv[0], uv[1]));
+            }
+            "f" => {
+                if rest.len() < 3 {
+                    return Err(error(format!("a face needs at least 3 corners, found {}", rest.len())));
+                }
+                let corners = rest
+                    .iter()
+                    .map(|corner| parse_corner(corner, &mesh))
+                    .collect::<Result<Vec<_>, String>>()
+                    .map_err(error)?;
+                // only use normals and uvs if every corner has them
+                let has_uvs = corners.iter().all(|corner| corner.1.is_some());
+                let has_normals = corners.iter().all(|corner| corner.2.is_some());
+                let points: Vec<Point3> = corners.iter().map(|corner| mesh.positions[corner.0]).collect();
+                for triangle in triangulate(&points) {
+                    mesh.faces.push(Face {
+                        positions: triangle.map(|i| corners[i].0),
+                        uvs: has_uvs.then(|| triangle.map(|i| corners[i].1.unwrap_or(0))),
+                        normals: has_normals.then(|| triangle.map(|i| corners[i].2.unwrap_or(0))),
+                        material,
+                        group,
+                    });
+                }
+            }
+            "g" | "o" => {
+                let name = if rest.is_empty() { "default".to_string() } else { rest.join(" ") };
+                group = match mesh.groups.iter().position(|existing| *existing == name) {
+                    Some(index) => index,
+                    None => {
+                        mesh.groups.push(name);
+                        mesh.groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => {
+                if rest.is_empty() {
+                    return Err(error("mtllib needs a file name".to_string()));
+                }
+                // names can have spaces in them, try the whole thing before splitting it up
+                let joined = rest.join(" ");
+                let names: Vec<&str> = if directory.join(&joined).is_file() { vec![joined.as_str()] } else { rest.clone() };
+                for name in names {
+                    let path = directory.join(name);
+                    let text = fs::read_to_string(&path)
+                        .map_err(|e| error(format!("{}: {}", path.display(), e)))?;
+                    parse_mtl(&text, &mut library)
+                        .map_err(|e| error(format!("{}:{}", path.display(), e)))?;
+                }
+            }
+            "usemtl" => {
+                let name = rest.join(" ");
+                material = match material_indices.get(&name) {
+                    Some(&index) => index,
+                    None => {
+                        let found = library
+                            .get(&name)
+                            .ok_or_else(|| error(format!("no material named \"{}\" in the mtllibs so far", name)))?;
+                        mesh.materials.push(found.clone());
+                        material_indices.insert(name, mesh.materials.len() - 1);
+                        mesh.materials.len() - 1
+                    }
+                };
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+// lines with a trailing backslash carry on onto the next one. numbered from 1 by where
+// they started
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending: Option<(usize, String)> = None;
+    for (index, line) in text.lines().enumerate() {
+        let (number, mut joined) = pending.take().unwrap_or((index + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(start) => {
+                joined.push_str(start);
+                joined.push(' ');
+                pending = Some((number, joined));
+            }
+            None => {
+                joined.push_str(line);
+                lines.push((number, joined));
+            }
+        }
+    }
+    lines.extend(pending);
+    lines
+}
+
+// between `min` and `max` numbers, anything past max (like the w in "v x y z w") is ignored.
+// missing ones up to max come back as 0
+fn numbers(fields: &[&str], min: usize, max: usize) -> Result<[f64; 3], String> {
+    if fields.len() < min {
+        return Err(format!("expected at least {} numbers, found {}", min, fields.len()));
+    }
+    let mut values = [0.0f64; 3];
+    for (value, field) in values.iter_mut().zip(fields.iter().take(max)) {
+        *value = field.parse().map_err(|_| format!("\"{}\" isn't a number", field))?;
+        if !value.is_finite() {
+            return Err(format!("\"{}\" isn't a finite number", field));
+        }
+    }
+    Ok(values)
+}
+
+// "v", "v/vt", "v//vn" or "v/vt/vn", 1 based, negative counts back from the latest one
+fn parse_corner(corner: &str, mesh: &Mesh) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = corner.split('/');
+    let index = |part: Option<&str>, count: usize, what: &str| -> Result<Option<usize>, String> {
+        let Some(part) = part.filter(|part| !part.is_empty()) else {
+            return Ok(None);
+        };
+        let value: i64 = part.parse().map_err(|_| format!("bad {} index \"{}\"", what, part))?;
+        let resolved = if value > 0 { value - 1 } else { count as i64 + value };
+        if value == 0 || resolved < 0 || resolved >= count as i64 {
+            return Err(format!("{} index {} is out of range, there are {} so far", what, value, count));
+        }
+        Ok(Some(resolved as usize))
+    };
+    let position = index(parts.next(), mesh.positions.len(), "vertex")?
+        .ok_or_else(|| format!("face corner \"{}\" has no vertex", corner))?;
+    let uv = index(parts.next(), mesh.uvs.len(), "texture coordinate")?;
+    let normal = index(parts.next(), mesh.normals.len(), "normal")?;
+    if parts.next().is_some() {
+        return Err(format!("bad face corner \"{}\"", corner));
+    }
+    Ok((position, uv, normal))
+}
+
+// the bits of an mtl material we look at
+struct MtlMaterial {
+    diffuse: Color3,
+    specular: Color3,
+    emission: Color3,
+    transmission: Color3,
+    shininess: f64,
+    refraction_index: f64,
+    opacity: f64,
+    illum: u32,
+    metallic: Option<f64>,
+    roughness: Option<f64>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Color3::new(0.8, 0.8, 0.8),
+            specular: Color3::new(0.0, 0.0, 0.0),
+            emission: Color3::new(0.0, 0.0, 0.0),
+            transmission: Color3::new(1.0, 1.0, 1.0),
+            shininess: 0.0,
+            refraction_index: 1.5,
+            opacity: 1.0,
+            illum: 2,
+            metallic: None,
+            roughness: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn to_material(&self) -> Arc<dyn Material> {
+        let e = self.emission;
+        if e.x() > 0.0 || e.y() > 0.0 || e.z() > 0.0 {
+            return Arc::new(DiffuseLight::new(e));
+        }
+        if matches!(self.illum, 4 | 6 | 7 | 9) || self.opacity < 1.0 {
+            return Arc::new(Dielectric::new(self.transmission, self.refraction_index));
+        }
+        if self.illum == 3 || self.metallic.is_some_and(|metallic| metallic >= 0.5) {
+            let s = self.specular;
+            let albedo = if s.x() > 0.0 || s.y() > 0.0 || s.z() > 0.0 { s } else { self.diffuse };
+            // blinn-phong exponents go roughly with 2 / roughness^2 - 2
+            let fuzz = self.roughness.unwrap_or_else(|| (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt());
+            return Arc::new(Metal::new(albedo, fuzz.clamp(0.0, 1.0)));
+        }
+        Arc::new(Lambertian::new(self.diffuse))
+    }
+}
+
+fn parse_mtl(text: &str, library: &mut HashMap<String, Arc<dyn Material>>) -> io::Result<()> {
+    let mut current: Option<(String, MtlMaterial)> = None;
+    for (line_number, line) in logical_lines(text) {
+        let error = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", line_number, message));
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let Some(keyword) = fields.next() else {
+            continue;
+        };
+        let rest: Vec<&str> = fields.collect();
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                library.insert(name, material.to_material());
+            }
+            current = Some((rest.join(" "), MtlMaterial::default()));
+            continue;
+        }
+        let Some((_, material)) = current.as_mut() else {
+            // stray statements before the first newmtl don't belong to anything
+            continue;
+        };
+        // "Kd 0.5" is shorthand for a grey
+        let color = |rest: &[&str]| -> io::Result<Color3> {
+            let values = numbers(rest, 1, 3).map_err(error)?;
+            Ok(if rest.len() < 3 { Color3::new(values[0], values[0], values[0]) } else { values.into() })
+        };
+        let number = |rest: &[&str]| -> io::Result<f64> { Ok(numbers(rest, 1, 1).map_err(error)?[0]) };
+        match keyword {
+            "Kd" => material.diffuse = color(&rest)?,
+            "Ks" => material.specular = color(&rest)?,
+            "Ke" => material.emission = color(&rest)?,
+            "Tf" => material.transmission = color(&rest)?,
+            "Ns" => material.shininess = number(&rest)?,
+            "Ni" => material.refraction_index = number(&rest)?.max(1.0),
+            "d" => material.opacity = number(&rest)?,
+            "Tr" => material.opacity = 1.0 - number(&rest)?,
+            "illum" => material.illum = number(&rest)? as u32,
+            "Pm" => material.metallic = Some(number(&rest)?),
+            "Pr" => material.roughness = Some(number(&rest)?),
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        library.insert(name, material.to_material());
+    }
+    Ok(())
+}
@@ -1,141 +1,1270 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::material::Material;
+use crate::rand::Rand;
 use crate::vec3::*;
 use crate::ray3::Ray3;
-use crate::rand::Rand;
 
-pub trait HittableMaterial {
-    fn hit_it(&self, ray: &Ray3) -> f64;
-    fn scatter(&self, ray_in: &Ray3, intersection_point_t: f64, return_ray: &mut Ray3, randomizer: &mut Rand) -> Vec3;
+// everything we know about a ray hitting something, so materials don't have to go back
+// to the shape to work out where and how it got hit
+pub struct HitRecord<'a> {
+    pub point: Point3,
+    // always points against the incoming ray, front_face says which side we came from
+    pub normal: Vec3,
+    pub t: f64,
+    pub front_face: bool,
+    // surface coordinates, both in [0, 1]
+    pub u: f64,
+    pub v: f64,
+    pub material: &'a dyn Material,
+}
+
+impl<'a> HitRecord<'a> {
+    // outward_normal has to be unit length
+    pub fn new(
+        ray: &Ray3,
+        t: f64,
+        outward_normal: Vec3,
+        (u, v): (f64, f64),
+        material: &'a dyn Material,
+    ) -> Self {
+        let front_face = ray.direction().dot(&outward_normal) < 0.0;
+        let normal = if front_face { outward_normal } else { -outward_normal };
+        Self {
+            point: ray.at(t),
+            normal,
+            t,
+            front_face,
+            u,
+            v,
+            material,
+        }
+    }
+}
+
+// geometry. send + sync so one scene can be shared by all the render threads
+pub trait Hittable: Send + Sync {
+    // closest hit with t inside (t_min, t_max), if there is one
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self) -> Aabb;
+
+    // whether the scene should sample this directly as a light. shapes that can't sample
+    // themselves keep the default and only show up in the image when a ray stumbles on them
+    fn is_light(&self) -> bool {
+        false
+    }
+
+    // solid angle density of `random` picking `direction` from `origin`, 0 if it can't
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.0
+    }
+
+    // a direction from `origin` towards some point on the shape
+    fn random(&self, _origin: &Point3, _randomizer: &mut Rand) -> Vec3 {
+        Vec3::new(1.0, 0.0, 0.0)
+    }
 }
 
-pub struct LambertianSphere {
+pub struct Sphere {
     pub center: Point3,
     pub radius: f64,
-    pub albedo: Color3// this is a color
+    pub material: Arc<dyn Material>,
 }
 
-impl LambertianSphere {
-    pub fn new(center: Point3, radius: f64, albedo: Color3) -> Self {
-        Self { center, radius, albedo }
+impl Sphere {
+    pub fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self { center, radius, material }
+    }
+
+    // u goes around the y axis starting from -x, v goes from the bottom (y = -1) to the top
+    fn uv(outward_normal: &Vec3) -> (f64, f64) {
+        let theta = (-outward_normal.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-outward_normal.z()).atan2(outward_normal.x()) + PI;
+        (phi / (2.0 * PI), theta / PI)
     }
 }
 
-impl HittableMaterial for LambertianSphere {
-    fn hit_it(&self, ray: &Ray3) -> f64 {
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let center_diff = self.center - ray.origin();
         let a = ray.direction().dot(&ray.direction());
-        let b = -2.0 * ray.direction().dot(&center_diff);
+        let h = ray.direction().dot(&center_diff);
         let c = center_diff.dot(&center_diff) - self.radius * self.radius;
-        let discriminant = b * b - 4.0 * a * c;
+        let discriminant = h * h - a * c;
         if discriminant < 0.0 {
-            return -1.0;
+            return None;
         }
-        return ( -b - discriminant.sqrt() ) / 2.0 * a;
+        let sqrt_discriminant = discriminant.sqrt();
+
+        // try the near root first, if we're inside the sphere the far one is the one we see
+        let mut t = (h - sqrt_discriminant) / a;
+        if t <= t_min || t >= t_max {
+            t = (h + sqrt_discriminant) / a;
+            if t <= t_min || t >= t_max {
+                return None;
+            }
+        }
+        let outward_normal = (ray.at(t) - self.center) / self.radius;
+        Some(HitRecord::new(ray, t, outward_normal, Self::uv(&outward_normal), self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - r, self.center + r)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
     }
-    fn scatter(&self, ray_in: &Ray3, intersection_point_t: f64, return_ray: &mut Ray3, randomizer: &mut Rand) -> Vec3 {
-        // returns the albedo color shit
-        let mut N = (ray_in.at(intersection_point_t) - self.center).unit_vector();
-        if N.dot(&ray_in.direction()) > 0.0 {
-            N = Vec3::new(0.0, 0.0, 0.0) - N;
+
+    // uniform over the cone of directions the sphere covers from origin. from inside the
+    // sphere there is no cone, so no light sampling either
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let distance_squared = (self.center - *origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return 0.0;
         }
-        let mut scatter_direction_vector = N + Vec3::random_unit_vector(randomizer);
-        if scatter_direction_vector.near_zero() {
-            scatter_direction_vector = N;
+        if self.hit(&Ray3::new(*origin, *direction), 0.001, f64::INFINITY).is_none() {
+            return 0.0;
         }
-        *return_ray = Ray3::new(ray_in.at(intersection_point_t), scatter_direction_vector);
-        return self.albedo;
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        let to_center = self.center - *origin;
+        let distance_squared = to_center.length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return Vec3::random_unit_vector(randomizer);
+        }
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+        let cos_theta = 1.0 + randomizer.next_f64() * (cos_theta_max - 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * randomizer.next_f64();
+
+        let w = to_center.unit_vector();
+        let (u, v) = orthonormal_basis(&w);
+        (phi.cos() * sin_theta) * u + (phi.sin() * sin_theta) * v + cos_theta * w
     }
 }
 
-pub struct MetalSphere {
-    pub center: Point3,
-    pub radius: f64,
-    pub albedo: Color3, // this is a color
-    pub fuzz: f64, // how much to fuzz the reflections
+// ray/triangle intersection, gives back t and the barycentric weights of the second and
+// third vertex. plain möller-trumbore works the barycentrics out differently for the two
+// triangles on either side of an edge, so a ray straight through the edge can round its way
+// out of both and slip through the mesh. this is the watertight version instead (woop,
+// benthin and wald 2013): move the ray to the origin pointing down +z, then a shared edge
+// gives exactly the same edge function (up to sign) in both triangles and edges count as
+// inside, so one of them always gets it
+pub fn intersect_triangle(
+    ray: &Ray3,
+    vertices: &[Point3; 3],
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    let direction = ray.direction();
+    // the largest direction component becomes z, the other two follow around
+    let kz = if direction.x().abs() > direction.y().abs() {
+        if direction.x().abs() > direction.z().abs() { 0 } else { 2 }
+    } else if direction.y().abs() > direction.z().abs() {
+        1
+    } else {
+        2
+    };
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    if direction[kz] == 0.0 {
+        return None;
+    }
+
+    // shear so the ray runs along +z
+    let shear_x = -direction[kx] / direction[kz];
+    let shear_y = -direction[ky] / direction[kz];
+    let shear_z = 1.0 / direction[kz];
+    let transform = |vertex: &Point3| {
+        let p = *vertex - ray.origin();
+        (p[kx] + shear_x * p[kz], p[ky] + shear_y * p[kz], p[kz])
+    };
+    let (x0, y0, z0) = transform(&vertices[0]);
+    let (x1, y1, z1) = transform(&vertices[1]);
+    let (x2, y2, z2) = transform(&vertices[2]);
+
+    // which side of each edge the ray passes, all the same sign (or 0) means inside
+    let e0 = x1 * y2 - y1 * x2;
+    let e1 = x2 * y0 - y2 * x0;
+    let e2 = x0 * y1 - y0 * x1;
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let determinant = e0 + e1 + e2;
+    // edge on or no area
+    if determinant == 0.0 || !determinant.is_finite() {
+        return None;
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let t = (e0 * z0 + e1 * z1 + e2 * z2) * shear_z * inverse_determinant;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    Some((t, e1 * inverse_determinant, e2 * inverse_determinant))
 }
 
-impl MetalSphere {
-    pub fn new(center: Point3, radius: f64, albedo: Color3, fuzz: f64) -> Self {
-        Self { center, radius, albedo, fuzz }
+// builds the hit record for a triangle hit from intersect_triangle. front_face comes from
+// the winding (counter clockwise seen from the front), the shading normal and uv are
+// interpolated if there are per vertex ones
+pub fn triangle_hit_record<'a>(
+    ray: &Ray3,
+    vertices: &[Point3; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+    (t, b1, b2): (f64, f64, f64),
+    material: &'a dyn Material,
+) -> HitRecord<'a> {
+    let b0 = 1.0 - b1 - b2;
+    let geometric_normal = (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0])).unit_vector();
+    let uv = match uvs {
+        Some(uvs) => (
+            b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0,
+            b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1,
+        ),
+        None => (b1, b2),
+    };
+    let mut hit = HitRecord::new(ray, t, geometric_normal, uv, material);
+    if let Some(normals) = normals {
+        let mut shading_normal = b0 * normals[0] + b1 * normals[1] + b2 * normals[2];
+        if !shading_normal.near_zero() {
+            shading_normal = shading_normal.unit_vector();
+            // keep it on the same side as the winding says, whatever the file claims
+            if shading_normal.dot(&geometric_normal) < 0.0 {
+                shading_normal = -shading_normal;
+            }
+            hit.normal = if hit.front_face { shading_normal } else { -shading_normal };
+        }
     }
+    hit
 }
 
-impl HittableMaterial for MetalSphere {
-    fn hit_it(&self, ray: &Ray3) -> f64 {
-        let center_diff = self.center - ray.origin();
-        let a = ray.direction().dot(&ray.direction());
-        let b = -2.0 * ray.direction().dot(&center_diff);
-        let c = center_diff.dot(&center_diff) - self.radius * self.radius;
-        let discriminant = b * b - 4.0 * a * c;
-        if discriminant < 0.0 {
-            return -1.0;
+// area light sampling for a triangle, shared with mesh triangles: a uniform point on the
+// triangle, and the density of that as seen from origin
+pub fn triangle_pdf_value(vertices: &[Point3; 3], origin: &Point3, direction: &Vec3) -> f64 {
+    let ray = Ray3::new(*origin, *direction);
+    let Some((t, _, _)) = intersect_triangle(&ray, vertices, 0.001, f64::INFINITY) else {
+        return 0.0;
+    };
+    let cross = (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0]));
+    let area = 0.5 * cross.length();
+    // ray directions are unit length, so t is the distance
+    let cosine = (ray.direction().dot(&cross) / cross.length()).abs();
+    if area <= 0.0 || cosine <= 0.0 {
+        return 0.0;
+    }
+    t * t / (cosine * area)
+}
+
+pub fn triangle_random(vertices: &[Point3; 3], origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+    // fold the unit square onto the triangle
+    let mut b1 = randomizer.next_f64();
+    let mut b2 = randomizer.next_f64();
+    if b1 + b2 > 1.0 {
+        b1 = 1.0 - b1;
+        b2 = 1.0 - b2;
+    }
+    let point = vertices[0] + b1 * (vertices[1] - vertices[0]) + b2 * (vertices[2] - vertices[0]);
+    point - *origin
+}
+
+pub fn triangle_bounding_box(vertices: &[Point3; 3]) -> Aabb {
+    Aabb::new(vertices[0], vertices[0]).grow(&vertices[1]).grow(&vertices[2]).padded(1e-4)
+}
+
+pub struct Triangle {
+    // counter clockwise seen from the front
+    pub vertices: [Point3; 3],
+    // per vertex shading normals, the flat face normal if there aren't any
+    pub normals: Option<[Vec3; 3]>,
+    // per vertex texture coordinates, the barycentrics of the 2nd and 3rd vertex otherwise
+    pub uvs: Option<[(f64, f64); 3]>,
+    pub material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(a: Point3, b: Point3, c: Point3, material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: [a, b, c],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit = intersect_triangle(ray, &self.vertices, t_min, t_max)?;
+        Some(triangle_hit_record(
+            ray,
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+            hit,
+            self.material.as_ref(),
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        triangle_bounding_box(&self.vertices)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        triangle_pdf_value(&self.vertices, origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        triangle_random(&self.vertices, origin, randomizer)
+    }
+}
+
+// a flat surface going on forever through `point`, facing along `normal`. there's no box to
+// put around it, so the scene checks it against every ray instead of going through the bvh
+pub struct Plane {
+    pub point: Point3,
+    // unit length
+    pub normal: Vec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        Self { point, normal: normal.unit_vector(), material }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.dot(&ray.direction());
+        if denominator.abs() < 1e-12 {
+            return None;
         }
-        return ( -b - discriminant.sqrt() ) / 2.0 * a;
+        let t = self.normal.dot(&(self.point - ray.origin())) / denominator;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        // uvs repeat every unit along a pair of directions in the plane
+        let (tangent, bitangent) = orthonormal_basis(&self.normal);
+        let offset = ray.at(t) - self.point;
+        let uv = (offset.dot(&tangent).rem_euclid(1.0), offset.dot(&bitangent).rem_euclid(1.0));
+        Some(HitRecord::new(ray, t, self.normal, uv, self.material.as_ref()))
     }
-    fn scatter(&self, ray_in: &Ray3, intersection_point_t: f64, return_ray: &mut Ray3, randomizer: &mut Rand) -> Vec3 {
-        // returns the albedo color shit
-        let mut N = (ray_in.at(intersection_point_t) - self.center).unit_vector();
-        if N.dot(&ray_in.direction()) > 0.0 {
-            N = Vec3::new(0.0, 0.0, 0.0) - N;
+
+    // infinite in every direction, except across the plane when it lines up with an axis
+    fn bounding_box(&self) -> Aabb {
+        let mut min = Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        let mut max = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        for axis in 0..3 {
+            if self.normal[axis].abs() == 1.0 {
+                min[axis] = self.point[axis];
+                max[axis] = self.point[axis];
+            }
         }
-        let reflected = reflect(&ray_in.direction(), &N).unit_vector() + (self.fuzz * Vec3::random_unit_vector(randomizer));
-        
-        *return_ray = Ray3::new(ray_in.at(intersection_point_t), reflected);
-        return self.albedo;
+        Aabb::new(min, max).padded(1e-4)
     }
 }
 
+// a parallelogram with one corner at `corner` and edges `u` and `v` going off it. the front
+// is the side u x v points to, and the uvs run from 0 to 1 along the two edges
+pub struct Quad {
+    pub corner: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(corner: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        Self { corner, u, v, material }
+    }
+
+    // where `ray` crosses the plane of the quad, if that's inside the quad: t and how far
+    // along each edge
+    fn intersect(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+        let n = self.u.cross(&self.v);
+        let length_squared = n.length_squared();
+        if length_squared == 0.0 {
+            return None;
+        }
+        let denominator = n.dot(&ray.direction());
+        if denominator.abs() < 1e-12 * length_squared.sqrt() {
+            return None;
+        }
+        let t = n.dot(&(self.corner - ray.origin())) / denominator;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let offset = ray.at(t) - self.corner;
+        let alpha = n.dot(&offset.cross(&self.v)) / length_squared;
+        let beta = n.dot(&self.u.cross(&offset)) / length_squared;
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((t, alpha, beta))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (t, alpha, beta) = self.intersect(ray, t_min, t_max)?;
+        let normal = self.u.cross(&self.v).unit_vector();
+        Some(HitRecord::new(ray, t, normal, (alpha, beta), self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::new(self.corner, self.corner + self.u + self.v)
+            .union(&Aabb::new(self.corner + self.u, self.corner + self.v))
+            .padded(1e-4)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    // uniform over the area, turned into solid angle by distance^2 / cos
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray3::new(*origin, *direction);
+        let Some((t, _, _)) = self.intersect(&ray, 0.001, f64::INFINITY) else {
+            return 0.0;
+        };
+        let n = self.u.cross(&self.v);
+        let area = n.length();
+        // ray directions are unit length, so t is the distance
+        let cosine = (ray.direction().dot(&n) / area).abs();
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        t * t / (cosine * area)
+    }
+
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        let point = self.corner + randomizer.next_f64() * self.u + randomizer.next_f64() * self.v;
+        point - *origin
+    }
+}
+
+// the box between two opposite corners as six quads, all facing out
+pub fn cuboid(a: Point3, b: Point3, material: Arc<dyn Material>) -> Vec<Box<dyn Hittable>> {
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+    let sides = [
+        (Point3::new(min.x(), min.y(), max.z()), dx, dy),
+        (Point3::new(max.x(), min.y(), max.z()), -dz, dy),
+        (Point3::new(max.x(), min.y(), min.z()), -dx, dy),
+        (Point3::new(min.x(), min.y(), min.z()), dz, dy),
+        (Point3::new(min.x(), max.y(), max.z()), dx, -dz),
+        (Point3::new(min.x(), min.y(), min.z()), dx, dz),
+    ];
+    sides
+        .into_iter()
+        .map(|(corner, u, v)| Box::new(Quad::new(corner, u, v, material.clone())) as Box<dyn Hittable>)
+        .collect()
+}
+
+// the quadrics below (and the torus) all stand on the xz plane around the +y axis of their
+// own frame, with `center` the middle of the bottom. anything tilted needs a transform. the
+// sweep is in degrees and cuts them down to a wedge, counter clockwise looking down from
+// above starting at +x. u goes around with the sweep, v goes up (or out, for flat parts)
 
-pub struct GlassSphere {
+// angle around +y of a point at (x, z), in [0, 2 pi)
+fn sweep_angle(x: f64, z: f64) -> f64 {
+    (-z).atan2(x).rem_euclid(2.0 * PI)
+}
+
+// both roots of a t^2 + b t + c = 0, smallest first. written so the two don't cancel each
+// other out when b is much bigger than the rest
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+// where a ray, already in the shape's frame, crosses the flat ring at `height` between
+// `inner` and `outer` within `sweep` radians. gives back t, the angle and the radius
+#[allow(clippy::too_many_arguments)]
+fn hit_ring(
+    origin: &Vec3,
+    direction: &Vec3,
+    height: f64,
+    inner: f64,
+    outer: f64,
+    sweep: f64,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64)> {
+    if direction.y() == 0.0 {
+        return None;
+    }
+    let t = (height - origin.y()) / direction.y();
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    let x = origin.x() + t * direction.x();
+    let z = origin.z() + t * direction.z();
+    let radius = (x * x + z * z).sqrt();
+    if radius > outer || radius < inner {
+        return None;
+    }
+    let phi = sweep_angle(x, z);
+    if phi > sweep {
+        return None;
+    }
+    Some((t, phi, radius))
+}
+
+// light sampling for the shapes below, which pick a point uniformly over their area. a
+// direction can pass through the surface more than once and any of those points could have
+// been the one picked, so their densities add up, each turned into solid angle by
+// distance^2 / cos
+fn area_pdf_value(shape: &dyn Hittable, area: f64, origin: &Point3, direction: &Vec3) -> f64 {
+    let ray = Ray3::new(*origin, *direction);
+    let mut pdf = 0.0;
+    let mut t_min = 0.001;
+    // hits have to be past t_min, so this moves on every time round
+    while let Some(hit) = shape.hit(&ray, t_min, f64::INFINITY) {
+        let cosine = hit.normal.dot(&ray.direction()).abs();
+        if cosine > 0.0 {
+            pdf += hit.t * hit.t / (cosine * area);
+        }
+        t_min = hit.t;
+    }
+    pdf
+}
+
+// a point picked uniformly over the flat ring at `height` between `inner` and `outer`,
+// within `sweep` radians
+fn random_on_ring(height: f64, inner: f64, outer: f64, sweep: f64, randomizer: &mut Rand) -> Vec3 {
+    let radius = (inner * inner + randomizer.next_f64() * (outer * outer - inner * inner)).sqrt();
+    let phi = randomizer.next_f64() * sweep;
+    Vec3::new(radius * phi.cos(), height, -radius * phi.sin())
+}
+
+// a circle (or a ring with an inner radius, or a slice of either with a sweep) lying flat
+// at `center`, facing +y
+pub struct Disk {
     pub center: Point3,
     pub radius: f64,
-    pub albedo: Color3,
-    pub refraction_index: f64,
+    pub inner_radius: f64,
+    pub sweep: f64,
+    pub material: Arc<dyn Material>,
 }
 
-impl GlassSphere {
-    pub fn new(center: Point3, radius: f64, albedo: Color3, refraction_index: f64) -> Self {
-        Self { center, radius, albedo, refraction_index }
+impl Disk {
+    pub fn new(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self { center, radius, inner_radius: 0.0, sweep: 360.0, material }
     }
-    fn reflectance(&self, cosine: f64, refraction_index: f64) -> f64 {
-        let mut r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
-        r0 = r0 * r0;
-        return r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0);
+
+    // anything closer to the middle than this is a hole
+    pub fn with_inner_radius(mut self, inner_radius: f64) -> Self {
+        self.inner_radius = inner_radius;
+        self
+    }
+
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.sweep = degrees;
+        self
+    }
+
+    fn area(&self) -> f64 {
+        0.5 * self.sweep.to_radians() * (self.radius * self.radius - self.inner_radius * self.inner_radius)
     }
 }
 
-impl HittableMaterial for GlassSphere {
-    fn hit_it(&self, ray: &Ray3) -> f64 {
-        let center_diff = self.center - ray.origin();
-        let a = ray.direction().dot(&ray.direction());
-        let b = -2.0 * ray.direction().dot(&center_diff);
-        let c = center_diff.dot(&center_diff) - self.radius * self.radius;
-        let discriminant = b * b - 4.0 * a * c;
-        if discriminant < 0.0 {
-            return -1.0;
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let origin = ray.origin() - self.center;
+        let sweep = self.sweep.to_radians();
+        let (t, phi, radius) =
+            hit_ring(&origin, &ray.direction(), 0.0, self.inner_radius, self.radius, sweep, t_min, t_max)?;
+        // v runs from the inner edge out
+        let v = (radius - self.inner_radius) / (self.radius - self.inner_radius);
+        let uv = (phi / sweep, v);
+        Some(HitRecord::new(ray, t, Vec3::new(0.0, 1.0, 0.0), uv, self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, 0.0, self.radius);
+        Aabb::new(self.center - r, self.center + r).padded(1e-4)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        area_pdf_value(self, self.area(), origin, direction)
+    }
+
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        let point = random_on_ring(0.0, self.inner_radius, self.radius, self.sweep.to_radians(), randomizer);
+        self.center + point - *origin
+    }
+}
+
+// the round side of a cylinder from `center` up to `height`, with the two ends closed off
+// if it's capped
+pub struct Cylinder {
+    pub center: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub sweep: f64,
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    // open at both ends
+    pub fn new(center: Point3, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Self { center, radius, height, sweep: 360.0, capped: false, material }
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.sweep = degrees;
+        self
+    }
+}
+
+impl Cylinder {
+    // of the side, and of each cap if there are any
+    fn areas(&self) -> (f64, f64) {
+        let sweep = self.sweep.to_radians();
+        let cap = if self.capped { 0.5 * sweep * self.radius * self.radius } else { 0.0 };
+        (sweep * self.radius * self.height, cap)
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let o = ray.origin() - self.center;
+        let d = ray.direction();
+        let sweep = self.sweep.to_radians();
+        let mut closest: Option<(f64, Vec3, (f64, f64))> = None;
+
+        let a = d.x() * d.x() + d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z());
+        let c = o.x() * o.x() + o.z() * o.z() - self.radius * self.radius;
+        // straight up or down never touches the side
+        if let Some((t0, t1)) = solve_quadratic(a, b, c).filter(|_| a > 0.0) {
+            for t in [t0, t1] {
+                if t <= t_min || t >= t_max {
+                    continue;
+                }
+                let p = o + t * d;
+                let phi = sweep_angle(p.x(), p.z());
+                if p.y() < 0.0 || p.y() > self.height || phi > sweep {
+                    continue;
+                }
+                let normal = Vec3::new(p.x() / self.radius, 0.0, p.z() / self.radius);
+                closest = Some((t, normal, (phi / sweep, p.y() / self.height)));
+                break;
+            }
         }
-        return ( -b - discriminant.sqrt() ) / 2.0 * a;
+        if self.capped {
+            for (height, normal_y) in [(0.0, -1.0), (self.height, 1.0)] {
+                let t_max = closest.map_or(t_max, |(t, _, _)| t);
+                if let Some((t, phi, radius)) = hit_ring(&o, &d, height, 0.0, self.radius, sweep, t_min, t_max) {
+                    closest = Some((t, Vec3::new(0.0, normal_y, 0.0), (phi / sweep, radius / self.radius)));
+                }
+            }
+        }
+        let (t, normal, uv) = closest?;
+        Some(HitRecord::new(ray, t, normal, uv, self.material.as_ref()))
     }
-    fn scatter(&self, ray_in: &Ray3, intersection_point_t: f64, return_ray: &mut Ray3, randomizer: &mut Rand) -> Vec3 {
-        // returns the albedo color shit
-        let attenuation = Color3::new(1.0, 1.0, 1.0);
-        let mut ri = 1.0 / self.refraction_index;
 
-        let mut N = (ray_in.at(intersection_point_t) - self.center).unit_vector();
-        if N.dot(&ray_in.direction()) > 0.0 {
-            N = Vec3::new(0.0, 0.0, 0.0) - N;
-            ri = self.refraction_index;
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, 0.0, self.radius);
+        Aabb::new(self.center - r, self.center + r + Vec3::new(0.0, self.height, 0.0)).padded(1e-4)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let (side, cap) = self.areas();
+        area_pdf_value(self, side + 2.0 * cap, origin, direction)
+    }
+
+    // the side or one of the caps, in proportion to their areas
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        let (side, cap) = self.areas();
+        let sweep = self.sweep.to_radians();
+        let pick = randomizer.next_f64() * (side + 2.0 * cap);
+        let point = if pick < side {
+            let phi = randomizer.next_f64() * sweep;
+            let y = randomizer.next_f64() * self.height;
+            Vec3::new(self.radius * phi.cos(), y, -self.radius * phi.sin())
+        } else {
+            let height = if pick < side + cap { 0.0 } else { self.height };
+            random_on_ring(height, 0.0, self.radius, sweep, randomizer)
+        };
+        self.center + point - *origin
+    }
+}
+
+// a cone with its base of `radius` at `center` and its tip `height` above it. capping it
+// closes off the base
+pub struct Cone {
+    pub center: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub sweep: f64,
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    // open at the base
+    pub fn new(center: Point3, radius: f64, height: f64, material: Arc<dyn Material>) -> Self {
+        Self { center, radius, height, sweep: 360.0, capped: false, material }
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.sweep = degrees;
+        self
+    }
+}
+
+impl Cone {
+    // of the slanted side, and of the base if it's capped
+    fn areas(&self) -> (f64, f64) {
+        let sweep = self.sweep.to_radians();
+        let slant = (self.radius * self.radius + self.height * self.height).sqrt();
+        let cap = if self.capped { 0.5 * sweep * self.radius * self.radius } else { 0.0 };
+        (0.5 * sweep * self.radius * slant, cap)
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let o = ray.origin() - self.center;
+        let d = ray.direction();
+        let sweep = self.sweep.to_radians();
+        let mut closest: Option<(f64, Vec3, (f64, f64))> = None;
+
+        // x^2 + z^2 = (k (height - y))^2 with k the radius lost per unit of height
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let h = self.height - o.y();
+        let a = d.x() * d.x() + d.z() * d.z() - k2 * d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.z() * d.z() + k2 * h * d.y());
+        let c = o.x() * o.x() + o.z() * o.z() - k2 * h * h;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                if t <= t_min || t >= t_max {
+                    continue;
+                }
+                let p = o + t * d;
+                let phi = sweep_angle(p.x(), p.z());
+                // the equation has a mirror image cone above the tip, that doesn't count
+                if p.y() < 0.0 || p.y() > self.height || phi > sweep {
+                    continue;
+                }
+                // the gradient vanishes right at the tip, where straight up is as good as any
+                let gradient = Vec3::new(p.x(), k2 * (self.height - p.y()), p.z());
+                let normal = if gradient.length_squared() > 0.0 {
+                    gradient.unit_vector()
+                } else {
+                    Vec3::new(0.0, 1.0, 0.0)
+                };
+                closest = Some((t, normal, (phi / sweep, p.y() / self.height)));
+                break;
+            }
         }
+        if self.capped {
+            let t_max = closest.map_or(t_max, |(t, _, _)| t);
+            if let Some((t, phi, radius)) = hit_ring(&o, &d, 0.0, 0.0, self.radius, sweep, t_min, t_max) {
+                closest = Some((t, Vec3::new(0.0, -1.0, 0.0), (phi / sweep, radius / self.radius)));
+            }
+        }
+        let (t, normal, uv) = closest?;
+        Some(HitRecord::new(ray, t, normal, uv, self.material.as_ref()))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::new(self.radius, 0.0, self.radius);
+        Aabb::new(self.center - r, self.center + r + Vec3::new(0.0, self.height, 0.0)).padded(1e-4)
+    }
 
-        let unit_direction = ray_in.direction(); // should already be a unit vec
-        let cos_theta = f64::min((-unit_direction).dot(&N), 1.0);
-        let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
-        let cannot_refract = ri * sin_theta > 1.0;
-        let direction = if (cannot_refract || self.reflectance(cos_theta, ri) > randomizer.next() as f64){
-            reflect(&unit_direction, &N)
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let (side, cap) = self.areas();
+        area_pdf_value(self, side + cap, origin, direction)
+    }
+
+    // the side's area grows linearly going down from the tip, so the distance from the tip
+    // goes with the square root
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        let (side, cap) = self.areas();
+        let sweep = self.sweep.to_radians();
+        let point = if randomizer.next_f64() * (side + cap) < side {
+            let from_tip = randomizer.next_f64().sqrt();
+            let phi = randomizer.next_f64() * sweep;
+            let radius = from_tip * self.radius;
+            Vec3::new(radius * phi.cos(), self.height * (1.0 - from_tip), -radius * phi.sin())
         } else {
-            refract(&unit_direction, &N, ri)
+            random_on_ring(0.0, 0.0, self.radius, sweep, randomizer)
         };
-        *return_ray = Ray3::new(ray_in.at(intersection_point_t), direction);
-        return self.albedo;
+        self.center + point - *origin
+    }
+}
+
+// a ring doughnut lying flat around `center`: a circle of `minor_radius` swept around the
+// y axis at `major_radius`. unlike the others `center` is its middle, not its bottom. v goes
+// around the tube starting from the outside edge and heading up
+pub struct Torus {
+    pub center: Point3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub sweep: f64,
+    pub material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64, material: Arc<dyn Material>) -> Self {
+        Self { center, major_radius, minor_radius, sweep: 360.0, material }
+    }
+
+    pub fn with_sweep(mut self, degrees: f64) -> Self {
+        self.sweep = degrees;
+        self
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let d = ray.direction();
+        let dd = d.dot(&d);
+        let (big, small) = (self.major_radius, self.minor_radius);
+        // start from the point on the ray closest to the middle, so the quartic is solved
+        // near its roots instead of wherever the ray happened to start
+        let o = ray.origin() - self.center;
+        let shift = -o.dot(&d) / dd;
+        let o = o + shift * d;
+        let outer = big + small;
+        if o.length_squared() > outer * outer {
+            return None;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) with p = o + t d, expanded in t
+        let e = o.dot(&o) - big * big - small * small;
+        let f = o.dot(&d);
+        let four_r2 = 4.0 * big * big;
+        let coefficients = [
+            e * e - four_r2 * (small * small - o.y() * o.y()),
+            4.0 * f * e + 2.0 * four_r2 * o.y() * d.y(),
+            2.0 * dd * e + 4.0 * f * f + four_r2 * d.y() * d.y(),
+            4.0 * dd * f,
+            dd * dd,
+        ];
+        let sweep = self.sweep.to_radians();
+        for root in solve_quartic(&coefficients) {
+            let t = root + shift;
+            if t <= t_min || t >= t_max {
+                continue;
+            }
+            let p = o + root * d;
+            let phi = sweep_angle(p.x(), p.z());
+            if phi > sweep {
+                continue;
+            }
+            // straight out from the nearest point on the circle through the middle of the tube
+            let ring = (p.x() * p.x() + p.z() * p.z()).sqrt();
+            let normal = if ring > 0.0 {
+                (p - Vec3::new(big * p.x() / ring, 0.0, big * p.z() / ring)).unit_vector()
+            } else {
+                Vec3::new(0.0, p.y().signum(), 0.0)
+            };
+            let theta = p.y().atan2(ring - big).rem_euclid(2.0 * PI);
+            return Some(HitRecord::new(ray, t, normal, (phi / sweep, theta / (2.0 * PI)), self.material.as_ref()));
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let outer = self.major_radius + self.minor_radius;
+        let r = Vec3::new(outer, self.minor_radius, outer);
+        Aabb::new(self.center - r, self.center + r)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let area = self.sweep.to_radians() * self.minor_radius * 2.0 * PI * self.major_radius;
+        area_pdf_value(self, area, origin, direction)
+    }
+
+    // the outside of the tube has more area than the inside, in proportion to how far it is
+    // from the axis, so angles around the tube get kept with that probability
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        let (big, small) = (self.major_radius, self.minor_radius);
+        let theta = loop {
+            let theta = randomizer.next_f64() * 2.0 * PI;
+            if randomizer.next_f64() * (big + small) <= (big + small * theta.cos()).abs() {
+                break theta;
+            }
+        };
+        let phi = randomizer.next_f64() * self.sweep.to_radians();
+        let ring = big + small * theta.cos();
+        self.center + Vec3::new(ring * phi.cos(), small * theta.sin(), -ring * phi.sin()) - *origin
+    }
+}
+
+// real roots of c[4] x^4 + c[3] x^3 + c[2] x^2 + c[1] x + c[0] = 0, smallest first. ferrari's
+// method gets them close and a few newton steps on the original polynomial clean up what
+// it loses to rounding
+fn solve_quartic(c: &[f64; 5]) -> Vec<f64> {
+    if c[4] == 0.0 {
+        return Vec::new();
+    }
+    let (b, cc, d, e) = (c[3] / c[4], c[2] / c[4], c[1] / c[4], c[0] / c[4]);
+    // x = y - b / 4 gets rid of the cubic term: y^4 + p y^2 + q y + r = 0
+    let b2 = b * b;
+    let p = cc - 3.0 * b2 / 8.0;
+    let q = d - b * cc / 2.0 + b2 * b / 8.0;
+    let r = e - b * d / 4.0 + b2 * cc / 16.0 - 3.0 * b2 * b2 / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    let mut push_quadratic = |a1: f64, a0: f64| {
+        if let Some((y0, y1)) = solve_quadratic(1.0, a1, a0) {
+            roots.push(y0 - b / 4.0);
+            roots.push(y1 - b / 4.0);
+        }
+    };
+    // m from the resolvent cubic makes both sides of
+    // (y^2 + p/2 + m)^2 = 2m y^2 - q y + m^2 + m p + p^2/4 - r a perfect square
+    let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+    if m <= 1e-12 * (1.0 + p.abs()) {
+        // no usable m means q is about 0, and it's a quadratic in y^2
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1] {
+                if z >= 0.0 {
+                    push_quadratic(0.0, -z);
+                }
+            }
+        }
+    } else {
+        let s = (2.0 * m).sqrt();
+        push_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        push_quadratic(s, p / 2.0 + m - q / (2.0 * s));
+    }
+
+    let polynomial = |x: f64| (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+    let derivative = |x: f64| ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+    for root in roots.iter_mut() {
+        for _ in 0..3 {
+            let slope = derivative(*root);
+            if slope == 0.0 {
+                break;
+            }
+            *root -= polynomial(*root) / slope;
+        }
+    }
+    roots.retain(|root| root.is_finite());
+    roots.sort_by(|a, b| a.total_cmp(b));
+    roots
+}
+
+// largest real root of m^3 + a m^2 + b m + c = 0
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // m = t - a / 3 leaves t^3 + p t + q = 0
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    let t = if discriminant >= 0.0 {
+        let s = discriminant.sqrt();
+        (-q / 2.0 + s).cbrt() + (-q / 2.0 - s).cbrt()
+    } else {
+        // three real roots, the k = 0 one of the trigonometric form is the largest
+        let radius = (-p / 3.0).sqrt();
+        2.0 * radius * ((3.0 * q / (2.0 * p * radius)).clamp(-1.0, 1.0).acos() / 3.0).cos()
+    };
+    let mut m = t - a / 3.0;
+    for _ in 0..2 {
+        let slope = (3.0 * m + 2.0 * a) * m + b;
+        if slope == 0.0 {
+            break;
+        }
+        m -= (((m + a) * m + b) * m + c) / slope;
+    }
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Color3::new(0.5, 0.5, 0.5)))
+    }
+
+    fn ray(origin: [f64; 3], direction: [f64; 3]) -> Ray3 {
+        Ray3::new(Point3::from(origin), Vec3::from(direction))
+    }
+
+    // the hit has to be at distance t with the given outward normal, uvs in range and the
+    // point inside the bounding box
+    fn expect_hit(name: &str, shape: &dyn Hittable, ray: Ray3, t: f64, outward_normal: [f64; 3]) {
+        let Some(record) = shape.hit(&ray, 0.001, f64::INFINITY) else {
+            panic!("{}: expected a hit at t = {}, got nothing", name, t);
+        };
+        assert!((record.t - t).abs() < EPSILON, "{}: expected t = {}, got {}", name, t, record.t);
+        let normal = if record.front_face { record.normal } else { -record.normal };
+        let expected = Vec3::from(outward_normal);
+        assert!((normal - expected).length() < 1e-6, "{}: expected normal {:?}, got {:?}", name, expected, normal);
+        assert!((0.0..=1.0).contains(&record.u) && (0.0..=1.0).contains(&record.v), "{}: uv out of range", name);
+        let bounds = shape.bounding_box();
+        for axis in 0..3 {
+            assert!(
+                record.point[axis] >= bounds.min()[axis] - EPSILON && record.point[axis] <= bounds.max()[axis] + EPSILON,
+                "{}: hit point {:?} is outside the bounding box",
+                name,
+                record.point
+            );
+        }
+    }
+
+    fn expect_miss(name: &str, shape: &dyn Hittable, ray: Ray3) {
+        if let Some(record) = shape.hit(&ray, 0.001, f64::INFINITY) {
+            panic!("{}: expected a miss, got a hit at t = {}", name, record.t);
+        }
+    }
+
+    #[test]
+    fn disk_hits() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let ring = Disk::new(origin, 1.0, material()).with_inner_radius(0.5);
+        expect_hit("ring from above", &ring, ray([0.75, 1.0, 0.0], [0.0, -1.0, 0.0]), 1.0, [0.0, 1.0, 0.0]);
+        expect_hit("ring from below", &ring, ray([-0.75, -2.0, 0.0], [0.0, 1.0, 0.0]), 2.0, [0.0, 1.0, 0.0]);
+        expect_miss("ring through the hole", &ring, ray([0.0, 1.0, 0.0], [0.0, -1.0, 0.0]));
+        expect_miss("ring outside", &ring, ray([2.0, 1.0, 0.0], [0.0, -1.0, 0.0]));
+        // a quarter, from +x round to -z
+        let quarter = Disk::new(origin, 1.0, material()).with_sweep(90.0);
+        expect_hit("quarter inside the wedge", &quarter, ray([0.5, 1.0, -0.5], [0.0, -1.0, 0.0]), 1.0, [0.0, 1.0, 0.0]);
+        expect_miss("quarter outside the wedge", &quarter, ray([0.5, 1.0, 0.5], [0.0, -1.0, 0.0]));
+    }
+
+    #[test]
+    fn cylinder_hits() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let tube = Cylinder::new(origin, 1.0, 2.0, material());
+        expect_hit("open side", &tube, ray([0.0, 1.0, -5.0], [0.0, 0.0, 1.0]), 4.0, [0.0, 0.0, -1.0]);
+        expect_hit("open from inside", &tube, ray([0.0, 1.0, 0.0], [1.0, 0.0, 0.0]), 1.0, [1.0, 0.0, 0.0]);
+        expect_miss("open down the middle", &tube, ray([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]));
+        expect_miss("open above", &tube, ray([0.0, 2.5, -5.0], [0.0, 0.0, 1.0]));
+        // halfway up the side is halfway along v
+        let record = tube.hit(&ray([0.0, 1.0, -5.0], [0.0, 0.0, 1.0]), 0.001, f64::INFINITY).unwrap();
+        assert!((record.v - 0.5).abs() < EPSILON);
+
+        let can = Cylinder::new(origin, 1.0, 2.0, material()).with_caps(true);
+        expect_hit("capped top", &can, ray([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]), 3.0, [0.0, 1.0, 0.0]);
+        expect_hit("capped bottom", &can, ray([0.5, -1.0, 0.0], [0.0, 1.0, 0.0]), 1.0, [0.0, -1.0, 0.0]);
+        // coming down at 45 degrees from just outside the rim, lands on the cap before the side
+        let s = 0.5f64.sqrt();
+        expect_hit("capped slanted", &can, ray([-1.5, 3.0, 0.0], [s, -s, 0.0]), 2f64.sqrt(), [0.0, 1.0, 0.0]);
+
+        // only the -z half is there
+        let half = Cylinder::new(origin, 1.0, 2.0, material()).with_sweep(180.0);
+        expect_hit("half front", &half, ray([0.0, 1.0, -5.0], [0.0, 0.0, 1.0]), 4.0, [0.0, 0.0, -1.0]);
+        expect_hit("half through the gap", &half, ray([0.0, 1.0, 5.0], [0.0, 0.0, -1.0]), 6.0, [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn cone_hits() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let cone = Cone::new(origin, 1.0, 1.0, material());
+        let n = 0.5f64.sqrt();
+        expect_hit("halfway up", &cone, ray([0.0, 0.5, -5.0], [0.0, 0.0, 1.0]), 4.5, [0.0, n, -n]);
+        expect_hit("tip", &cone, ray([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]), 4.0, [0.0, 1.0, 0.0]);
+        expect_miss("mirror image above the tip", &cone, ray([0.0, 1.5, -5.0], [0.0, 0.0, 1.0]));
+        expect_hit("from underneath, open", &cone, ray([0.0, -5.0, 0.0], [0.0, 1.0, 0.0]), 6.0, [0.0, 1.0, 0.0]);
+        let capped = Cone::new(origin, 1.0, 1.0, material()).with_caps(true);
+        expect_hit("from underneath, capped", &capped, ray([0.0, -5.0, 0.0], [0.0, 1.0, 0.0]), 5.0, [0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn torus_hits() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let torus = Torus::new(origin, 2.0, 0.5, material());
+        expect_hit("outer edge", &torus, ray([-5.0, 0.0, 0.0], [1.0, 0.0, 0.0]), 2.5, [-1.0, 0.0, 0.0]);
+        expect_hit("inner edge from the middle", &torus, ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]), 1.5, [-1.0, 0.0, 0.0]);
+        expect_hit("from above", &torus, ray([2.0, 5.0, 0.0], [0.0, -1.0, 0.0]), 4.5, [0.0, 1.0, 0.0]);
+        expect_hit("far side through the hole", &torus, ray([0.0, 0.0, 10.0], [0.0, 0.0, -1.0]), 7.5, [0.0, 0.0, 1.0]);
+        expect_miss("down the hole", &torus, ray([0.0, 5.0, 0.0], [0.0, -1.0, 0.0]));
+        expect_miss("just over the top", &torus, ray([-5.0, 0.51, 0.0], [1.0, 0.0, 0.0]));
+
+        // three quarters, the quarter between +z (270 degrees) and +x is missing. down the
+        // diagonal through the gap, the first thing in the way is the inside of the far side
+        let open = Torus::new(origin, 2.0, 0.5, material()).with_sweep(270.0);
+        let gap = 2f64.sqrt();
+        let n = 0.5f64.sqrt();
+        expect_miss("open gap", &open, ray([gap, 5.0, gap], [0.0, -1.0, 0.0]));
+        expect_hit("open past the gap", &open, ray([10.0, 0.0, 10.0], [-1.0, 0.0, -1.0]), 200f64.sqrt() + 1.5, [n, 0.0, n]);
+    }
+
+    // random rays aimed roughly at a torus, every hit the quartic finds has to be on it
+    #[test]
+    fn torus_hits_are_on_the_surface() {
+        let (big, small) = (2.0, 0.5);
+        let torus = Torus::new(Point3::new(0.0, 0.0, 0.0), big, small, material());
+        let mut randomizer = Rand::new_with_seed(7);
+        let mut hits = 0;
+        for _ in 0..20_000 {
+            let from = 6.0 * Vec3::random_unit_vector(&mut randomizer);
+            let target = Vec3::new(
+                3.0 * (randomizer.next_f64() - 0.5),
+                randomizer.next_f64() - 0.5,
+                3.0 * (randomizer.next_f64() - 0.5),
+            );
+            let Some(record) = torus.hit(&Ray3::new(from, target - from), 0.001, f64::INFINITY) else {
+                continue;
+            };
+            hits += 1;
+            let p = record.point;
+            let ring = (p.x() * p.x() + p.z() * p.z()).sqrt();
+            let distance = ((ring - big).powi(2) + p.y() * p.y()).sqrt() - small;
+            assert!(distance.abs() < 1e-6, "a torus hit is {} off the surface", distance);
+        }
+        assert!(hits > 2_000, "random rays should mostly hit the torus, only {} did", hits);
+    }
+
+    fn light() -> Arc<dyn Material> {
+        Arc::new(crate::material::DiffuseLight::new(Color3::new(4.0, 4.0, 4.0)))
+    }
+
+    // the shapes that get sampled by area, seen from outside, some of them covering
+    // themselves from there
+    fn area_lights() -> Vec<(&'static str, Box<dyn Hittable>)> {
+        let center = Point3::new(0.5, -0.5, 0.2);
+        vec![
+            ("disk", Box::new(Disk::new(center, 1.0, light()))),
+            ("ring", Box::new(Disk::new(center, 1.0, light()).with_inner_radius(0.4).with_sweep(250.0))),
+            ("cylinder", Box::new(Cylinder::new(center, 0.8, 1.5, light()))),
+            ("capped cylinder", Box::new(Cylinder::new(center, 0.8, 1.5, light()).with_caps(true).with_sweep(300.0))),
+            ("cone", Box::new(Cone::new(center, 0.8, 1.5, light()).with_caps(true))),
+            ("torus", Box::new(Torus::new(center, 1.0, 0.3, light()).with_sweep(270.0))),
+        ]
+    }
+
+    #[test]
+    fn area_lights_are_lights() {
+        for (name, shape) in area_lights() {
+            assert!(shape.is_light(), "an emissive {} should be sampled as a light", name);
+        }
+        assert!(!Disk::new(Point3::new(0.0, 0.0, 0.0), 1.0, material()).is_light());
+        assert!(!Torus::new(Point3::new(0.0, 0.0, 0.0), 1.0, 0.3, material()).is_light());
+    }
+
+    // a density over directions has to integrate to 1 over the sphere, and the directions
+    // random() hands out have to be ones pdf_value() gives weight to
+    #[test]
+    fn area_light_pdfs_match_their_samples() {
+        let origin = Point3::new(-1.5, 2.5, 3.0);
+        let mut randomizer = Rand::new_with_seed(3);
+        let samples = 200_000;
+        for (name, shape) in area_lights() {
+            let mut total = 0.0;
+            for _ in 0..samples {
+                total += shape.pdf_value(&origin, &Vec3::random_unit_vector(&mut randomizer));
+            }
+            let integral = total / samples as f64 * 4.0 * PI;
+            // grazing directions past the curved edges have huge densities, so this is noisy
+            assert!((integral - 1.0).abs() < 0.1, "the {} pdf integrates to {}", name, integral);
+
+            for _ in 0..1_000 {
+                let direction = shape.random(&origin, &mut randomizer);
+                // the point picked has to be one of the places the ray crosses the surface
+                let ray = Ray3::new(origin, direction);
+                let mut t_min = 0.001;
+                let mut on_surface = false;
+                while let Some(hit) = shape.hit(&ray, t_min, f64::INFINITY) {
+                    on_surface |= (hit.t - direction.length()).abs() < 1e-6;
+                    t_min = hit.t;
+                }
+                assert!(on_surface, "{} sampled {:?}, which isn't on it", name, origin + direction);
+                assert!(shape.pdf_value(&origin, &direction) > 0.0, "{} sampled a direction it doesn't cover", name);
+            }
+        }
+    }
+
+    fn assert_roots(coefficients: [f64; 5], expected: &[f64]) {
+        let roots = solve_quartic(&coefficients);
+        assert_eq!(roots.len(), expected.len(), "{:?} gave {:?}", coefficients, roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "{:?} gave {:?}", coefficients, roots);
+        }
+    }
+
+    #[test]
+    fn solve_quartic_finds_known_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4), and the same times 2
+        assert_roots([24.0, -50.0, 35.0, -10.0, 1.0], &[1.0, 2.0, 3.0, 4.0]);
+        assert_roots([48.0, -100.0, 70.0, -20.0, 2.0], &[1.0, 2.0, 3.0, 4.0]);
+        // (x^2 + 1)(x - 2)(x + 3), two of them complex
+        assert_roots([-6.0, 1.0, -5.0, 1.0, 1.0], &[-3.0, 2.0]);
+        // (x^2 + 1)(x^2 + 4), none real
+        assert_roots([4.0, 0.0, 5.0, 0.0, 1.0], &[]);
+        // (x^2 - 1)(x^2 - 4), no odd terms
+        assert_roots([4.0, 0.0, -5.0, 0.0, 1.0], &[-2.0, -1.0, 1.0, 2.0]);
+        // not a quartic at all
+        assert_roots([1.0, 2.0, 3.0, 4.0, 0.0], &[]);
     }
 }
@@ -0,0 +1,346 @@
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::mesh::{triangulate, Face, Mesh};
+use crate::vec3::{Point3, Vec3};
+
+// stanford .ply loader, ascii and binary in either byte order. reads positions (x, y, z),
+// normals (nx, ny, nz) and texture coordinates (u, v or s, t) from the vertex element and
+// polygons from the vertex_indices list of the face element, triangulating anything with
+// more than three corners. every other element and property is read past and dropped
+// http://paulbourke.net/dataformats/ply/
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl Kind {
+    fn from_name(name: &str) -> Option<Kind> {
+        match name {
+            "char" | "int8" => Some(Kind::Int8),
+            "uchar" | "uint8" => Some(Kind::UInt8),
+            "short" | "int16" => Some(Kind::Int16),
+            "ushort" | "uint16" => Some(Kind::UInt16),
+            "int" | "int32" => Some(Kind::Int32),
+            "uint" | "uint32" => Some(Kind::UInt32),
+            "float" | "float32" => Some(Kind::Float32),
+            "double" | "float64" => Some(Kind::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Kind::Int8 | Kind::UInt8 => 1,
+            Kind::Int16 | Kind::UInt16 => 2,
+            Kind::Int32 | Kind::UInt32 | Kind::Float32 => 4,
+            Kind::Float64 => 8,
+        }
+    }
+}
+
+enum Property {
+    Scalar { name: String, kind: Kind },
+    List { name: String, count: Kind, item: Kind },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Property::Scalar { name, .. } | Property::List { name, .. } => name,
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid_ply(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("not a usable ply: {}", message))
+}
+
+// every face gets `material`
+pub fn load(path: &str, material: Arc<dyn Material>) -> io::Result<Mesh> {
+    let data = fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    parse(&data, material).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+pub fn parse(data: &[u8], material: Arc<dyn Material>) -> io::Result<Mesh> {
+    let (format, elements, body_start) = parse_header(data)?;
+    let mut body = Body { data, position: body_start, format };
+
+    let mut mesh = Mesh::new();
+    mesh.materials.push(material);
+    mesh.groups.push("default".to_string());
+    let mut has_normals = false;
+    let mut has_uvs = false;
+    let mut polygons: Vec<Vec<usize>> = Vec::new();
+
+    for element in &elements {
+        let find = |names: &[&str]| element.properties.iter().position(|property| names.contains(&property.name()));
+        let columns = |names: [&[&str]; 3]| -> Option<[usize; 3]> {
+            Some([find(names[0])?, find(names[1])?, find(names[2])?])
+        };
+        match element.name.as_str() {
+            "vertex" => {
+                let position = columns([&["x"], &["y"], &["z"]])
+                    .ok_or_else(|| invalid_ply("vertices need x, y and z"))?;
+                let normal = columns([&["nx"], &["ny"], &["nz"]]);
+                let uv = find(&["u", "s", "texture_u", "texture_s"]).zip(find(&["v", "t", "texture_v", "texture_t"]));
+                has_normals = normal.is_some();
+                has_uvs = uv.is_some();
+                let used: Vec<usize> = position.iter().chain(normal.iter().flatten()).copied()
+                    .chain(uv.into_iter().flat_map(|(u, v)| [u, v]))
+                    .collect();
+                for number in 0..element.count {
+                    let values = body.read_element(element)?;
+                    if used.iter().any(|&column| !values[column].is_finite()) {
+                        return Err(invalid_ply(&format!("vertex {} has a value that isn't a finite number", number)));
+                    }
+                    let at = |columns: [usize; 3]| Vec3::new(values[columns[0]], values[columns[1]], values[columns[2]]);
+                    mesh.positions.push(at(position));
+                    if let Some(normal) = normal {
+                        mesh.normals.push(at(normal));
+                    }
+                    if let Some((u, v)) = uv {
+                        mesh.uvs.push((values[u], values[v]));
+                    }
+                }
+            }
+            "face" => {
+                let indices = find(&["vertex_indices", "vertex_index"])
+                    .ok_or_else(|| invalid_ply("faces need a vertex_indices list"))?;
+                if !matches!(element.properties[indices], Property::List { .. }) {
+                    return Err(invalid_ply("vertex_indices has to be a list"));
+                }
+                for _ in 0..element.count {
+                    let mut lists = body.read_lists(element)?;
+                    let polygon = lists
+                        .swap_remove(indices)
+                        .into_iter()
+                        .map(|index| {
+                            if index < 0.0 || index.fract() != 0.0 {
+                                return Err(invalid_ply(&format!("bad vertex index {}", index)));
+                            }
+                            Ok(index as usize)
+                        })
+                        .collect::<io::Result<Vec<usize>>>()?;
+                    polygons.push(polygon);
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    body.read_element(element)?;
+                }
+            }
+        }
+    }
+
+    // faces can come before the vertices they use, so they only get checked at the end
+    for (number, polygon) in polygons.iter().enumerate() {
+        if polygon.len() < 3 {
+            return Err(invalid_ply(&format!("face {} has {} corners, it needs at least 3", number, polygon.len())));
+        }
+        if let Some(index) = polygon.iter().find(|&&index| index >= mesh.positions.len()) {
+            return Err(invalid_ply(&format!(
+                "face {} uses vertex {}, there are only {}",
+                number,
+                index,
+                mesh.positions.len()
+            )));
+        }
+        let points: Vec<Point3> = polygon.iter().map(|&index| mesh.positions[index]).collect();
+        for triangle in triangulate(&points) {
+            let corners = triangle.map(|i| polygon[i]);
+            mesh.faces.push(Face {
+                positions: corners,
+                normals: has_normals.then_some(corners),
+                uvs: has_uvs.then_some(corners),
+                material: 0,
+                group: 0,
+            });
+        }
+    }
+    Ok(mesh)
+}
+
+// gives back the format, the elements in file order and where the data after end_header
+// starts
+fn parse_header(data: &[u8]) -> io::Result<(Format, Vec<Element>, usize)> {
+    let mut position = 0;
+    let mut next_line = || -> io::Result<String> {
+        let start = position;
+        let end = data[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map(|offset| start + offset)
+            .ok_or_else(|| invalid_ply("header ends early"))?;
+        position = end + 1;
+        Ok(String::from_utf8_lossy(&data[start..end]).trim_end().to_string())
+    };
+
+    if next_line()? != "ply" {
+        return Err(invalid_ply("missing the ply signature"));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        let line = next_line()?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let kind = |name: &str| Kind::from_name(name).ok_or_else(|| invalid_ply(&format!("unknown property type \"{}\"", name)));
+        match fields.as_slice() {
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["format", name, version] => {
+                if !version.starts_with('1') {
+                    return Err(invalid_ply(&format!("unsupported version {}", version)));
+                }
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid_ply(&format!("unknown format \"{}\"", name))),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid_ply(&format!("bad element count \"{}\"", count)))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_ply("property before any element"))?
+                .properties
+                .push(Property::List { name: name.to_string(), count: kind(count)?, item: kind(item)? }),
+            ["property", kind_name, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid_ply("property before any element"))?
+                .properties
+                .push(Property::Scalar { name: name.to_string(), kind: kind(kind_name)? }),
+            _ => return Err(invalid_ply(&format!("bad header line \"{}\"", line))),
+        }
+    }
+    let format = format.ok_or_else(|| invalid_ply("no format line"))?;
+    Ok((format, elements, position))
+}
+
+// walks through the data after the header one value at a time
+struct Body<'a> {
+    data: &'a [u8],
+    position: usize,
+    format: Format,
+}
+
+impl Body<'_> {
+    fn read(&mut self, kind: Kind) -> io::Result<f64> {
+        if self.format == Format::Ascii {
+            return self.read_ascii();
+        }
+        let size = kind.size();
+        let bytes = self
+            .data
+            .get(self.position..self.position + size)
+            .ok_or_else(|| invalid_ply("file is truncated"))?;
+        self.position += size;
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        // put the bytes in big endian order so one set of from_be_bytes covers both
+        if self.format == Format::BinaryLittleEndian {
+            buffer[..size].reverse();
+        }
+        let [a, b, c, d, e, f, g, h] = buffer;
+        Ok(match kind {
+            Kind::Int8 => i8::from_be_bytes([a]) as f64,
+            Kind::UInt8 => a as f64,
+            Kind::Int16 => i16::from_be_bytes([a, b]) as f64,
+            Kind::UInt16 => u16::from_be_bytes([a, b]) as f64,
+            Kind::Int32 => i32::from_be_bytes([a, b, c, d]) as f64,
+            Kind::UInt32 => u32::from_be_bytes([a, b, c, d]) as f64,
+            Kind::Float32 => f32::from_be_bytes([a, b, c, d]) as f64,
+            Kind::Float64 => f64::from_be_bytes([a, b, c, d, e, f, g, h]),
+        })
+    }
+
+    fn read_ascii(&mut self) -> io::Result<f64> {
+        let data = self.data;
+        while self.position < data.len() && data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        while self.position < data.len() && !data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(invalid_ply("file is truncated"));
+        }
+        let text = String::from_utf8_lossy(&data[start..self.position]);
+        text.parse().map_err(|_| invalid_ply(&format!("\"{}\" isn't a number", text)))
+    }
+
+    fn read_count(&mut self, kind: Kind) -> io::Result<usize> {
+        let count = self.read(kind)?;
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(invalid_ply(&format!("bad list length {}", count)));
+        }
+        Ok(count as usize)
+    }
+
+    // one value per property, lists are read past and come back as 0
+    fn read_element(&mut self, element: &Element) -> io::Result<Vec<f64>> {
+        let mut values = Vec::with_capacity(element.properties.len());
+        for property in &element.properties {
+            match *property {
+                Property::Scalar { kind, .. } => values.push(self.read(kind)?),
+                Property::List { count, item, .. } => {
+                    for _ in 0..self.read_count(count)? {
+                        self.read(item)?;
+                    }
+                    values.push(0.0);
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    // one list per property, scalars are read past and come back empty
+    fn read_lists(&mut self, element: &Element) -> io::Result<Vec<Vec<f64>>> {
+        let mut lists = Vec::with_capacity(element.properties.len());
+        for property in &element.properties {
+            match *property {
+                Property::Scalar { kind, .. } => {
+                    self.read(kind)?;
+                    lists.push(Vec::new());
+                }
+                Property::List { count, item, .. } => {
+                    let length = self.read_count(count)?;
+                    let mut list = Vec::new();
+                    for _ in 0..length {
+                        list.push(self.read(item)?);
+                    }
+                    lists.push(list);
+                }
+            }
+        }
+        Ok(lists)
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::deflate::zlib_compress;
+
+// png encoder, 8 or 16 bits per channel rgb. rows get the adaptive filter choice libpng
+// uses (smallest sum of absolute values) and then go through our own deflate
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+impl BitDepth {
+    fn bytes_per_channel(self) -> usize {
+        match self {
+            BitDepth::Eight => 1,
+            BitDepth::Sixteen => 2,
+        }
+    }
+}
+
+// crc32 as used by png (and zip and gzip), table built at compile time
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &byte in data {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc ^ 0xffffffff
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut crc_input = Vec::with_capacity(4 + data.len());
+    crc_input.extend_from_slice(kind);
+    crc_input.extend_from_slice(data);
+    out.write_all(&crc_input)?;
+    out.write_all(&crc32(&crc_input).to_be_bytes())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// runs filter `kind` over one row. bpp is bytes per pixel, previous is the unfiltered row
+// above (all zeros for the first row)
+fn filter_row(kind: u8, row: &[u8], previous: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    out.push(kind);
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predicted = match kind {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            _ => paeth(left, up, up_left),
+        };
+        out.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+// raw samples, big endian for 16 bit, row by row. width * height * 3 channels
+pub fn encode(width: u32, height: u32, bit_depth: BitDepth, samples: &[u8]) -> io::Result<Vec<u8>> {
+    let bpp = 3 * bit_depth.bytes_per_channel();
+    let stride = width as usize * bpp;
+    if width == 0 || height == 0 || samples.len() != stride * height as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "sample count doesn't match the image size"));
+    }
+
+    let mut filtered = Vec::with_capacity((stride + 1) * height as usize);
+    let zeros = vec![0u8; stride];
+    let mut candidate = Vec::with_capacity(stride + 1);
+    let mut best = Vec::with_capacity(stride + 1);
+    for y in 0..height as usize {
+        let row = &samples[y * stride..(y + 1) * stride];
+        let previous = if y == 0 { &zeros[..] } else { &samples[(y - 1) * stride..y * stride] };
+        let mut best_score = u64::MAX;
+        for kind in 0..5 {
+            candidate.clear();
+            filter_row(kind, row, previous, bpp, &mut candidate);
+            // treat bytes as signed, small magnitudes compress best
+            let score: u64 = candidate[1..].iter().map(|&b| (b as i8).unsigned_abs() as u64).sum();
+            if score < best_score {
+                best_score = score;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        filtered.extend_from_slice(&best);
+    }
+
+    let mut png = Vec::new();
+    png.extend_from_slice(&SIGNATURE);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.push(8 * bit_depth.bytes_per_channel() as u8);
+    header.push(2); // truecolor rgb
+    header.push(0); // deflate
+    header.push(0); // adaptive filtering
+    header.push(0); // no interlace
+    write_chunk(&mut png, b"IHDR", &header)?;
+
+    // samples are srgb encoded, rendering intent perceptual
+    write_chunk(&mut png, b"sRGB", &[0])?;
+
+    write_chunk(&mut png, b"IDAT", &zlib_compress(&filtered))?;
+    write_chunk(&mut png, b"IEND", &[])?;
+    Ok(png)
+}
+
+pub fn write(filename: &str, width: u32, height: u32, bit_depth: BitDepth, samples: &[u8]) -> io::Result<()> {
+    let png = encode(width, height, bit_depth, samples)?;
+    let mut file = BufWriter::new(File::create(filename)?);
+    file.write_all(&png)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::adler32;
+    use crate::deflate::tests::zlib_decompress;
+    use crate::rand::Rand;
+
+    fn be32(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    // checks the chunk crcs and the zlib wrapper along the way, gives back the header and
+    // the unfiltered samples
+    fn decode(png: &[u8]) -> (u32, u32, u8, Vec<u8>) {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut position = 8;
+        let mut header = Vec::new();
+        let mut compressed = Vec::new();
+        let mut kinds = Vec::new();
+        while position < png.len() {
+            let length = be32(&png[position..]) as usize;
+            let kind_and_data = &png[position + 4..position + 8 + length];
+            let crc = be32(&png[position + 8 + length..]);
+            assert_eq!(crc, crc32(kind_and_data), "bad crc on chunk {:?}", &kind_and_data[..4]);
+            let data = &kind_and_data[4..];
+            match &kind_and_data[..4] {
+                b"IHDR" => header = data.to_vec(),
+                b"IDAT" => compressed.extend_from_slice(data),
+                _ => {}
+            }
+            kinds.push(kind_and_data[..4].to_vec());
+            position += 12 + length;
+        }
+        assert_eq!(position, png.len());
+        assert_eq!(kinds.first().map(|kind| kind.as_slice()), Some(&b"IHDR"[..]));
+        assert_eq!(kinds.last().map(|kind| kind.as_slice()), Some(&b"IEND"[..]));
+
+        let (width, height, depth) = (be32(&header[0..]), be32(&header[4..]), header[8]);
+        assert_eq!(header[9..], [2, 0, 0, 0]);
+
+        let filtered = zlib_decompress(&compressed);
+
+        let bpp = 3 * depth as usize / 8;
+        let stride = width as usize * bpp;
+        assert_eq!(filtered.len(), (stride + 1) * height as usize);
+        let mut samples: Vec<u8> = Vec::with_capacity(stride * height as usize);
+        for (y, row) in filtered.chunks(stride + 1).enumerate() {
+            let start = samples.len();
+            for i in 0..stride {
+                let left = if i >= bpp { samples[start + i - bpp] } else { 0 };
+                let up = if y > 0 { samples[start + i - stride] } else { 0 };
+                let up_left = if y > 0 && i >= bpp { samples[start + i - stride - bpp] } else { 0 };
+                let predicted = match row[0] {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    4 => paeth(left, up, up_left),
+                    kind => panic!("unknown filter {}", kind),
+                };
+                samples.push(row[1 + i].wrapping_add(predicted));
+            }
+        }
+        (width, height, depth, samples)
+    }
+
+    // smooth gradients (so every filter gets picked somewhere), a flat patch (long matches)
+    // and noise (literals)
+    fn test_image(width: u32, height: u32, bytes_per_channel: usize, seed: u64) -> Vec<u8> {
+        let mut randomizer = Rand::new_with_seed(seed);
+        let mut samples = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                for channel in 0..3u32 {
+                    let value: u32 = if x < width / 3 {
+                        (x * 7 + y * 3 + channel * 50) * 97
+                    } else if x < 2 * width / 3 {
+                        0x1234
+                    } else {
+                        randomizer.next_u32()
+                    };
+                    let bytes = (value as u16).to_be_bytes();
+                    samples.extend_from_slice(&bytes[2 - bytes_per_channel..]);
+                }
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn round_trips_8_bit() {
+        for (width, height) in [(1, 1), (37, 23), (300, 200)] {
+            let samples = test_image(width, height, 1, width as u64);
+            let png = encode(width, height, BitDepth::Eight, &samples).unwrap();
+            assert_eq!(decode(&png), (width, height, 8, samples));
+        }
+    }
+
+    #[test]
+    fn round_trips_16_bit() {
+        for (width, height) in [(1, 1), (19, 11), (256, 130)] {
+            let samples = test_image(width, height, 2, width as u64);
+            let png = encode(width, height, BitDepth::Sixteen, &samples).unwrap();
+            assert_eq!(decode(&png), (width, height, 16, samples));
+        }
+    }
+
+    #[test]
+    fn rejects_the_wrong_sample_count() {
+        assert!(encode(2, 2, BitDepth::Eight, &[0; 11]).is_err());
+        assert!(encode(0, 2, BitDepth::Sixteen, &[]).is_err());
+    }
+}
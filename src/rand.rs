@@ -1,69 +1,75 @@
-use std::{
-    time::{SystemTime, UNIX_EPOCH},
-    io::{self, Read},
-    fs::File
-};
-// use rand;
+// pcg32 (pcg-xsh-rr 64/32), see https://www.pcg-random.org
+// same seed + same stream always gives the same numbers, which is what makes renders
+// reproducible. we hand one stream out per pixel so the image doesn't depend on how the
+// work got split between threads. streams that only differ in the increment turn out
+// correlated though, so the stream gets mixed into the starting state too
+const MULTIPLIER: u64 = 6364136223846793005;
+const DEFAULT_STREAM: u64 = 0xda3e39cb94b95bdb;
 
-fn random_u32() -> u32 {
-    let mut rng = File::open("/dev/urandom").unwrap();
-
-    let mut buffer = [0u8; 4];
-    rng.read_exact(&mut buffer).unwrap();
-    ((buffer[0] as u32) << 24) | ((buffer[1] as u32) << 16) | ((buffer[2] as u32) << 8) | (buffer[3] as u32)
-}
-
-fn random_f32() -> f32 {
-    let random_u32_number = random_u32();
-    random_u32_number as f32 / u32::MAX as f32
+#[derive(Clone, Debug)]
+pub struct Rand {
+    state: u64,
+    increment: u64,
 }
 
-pub struct Rand {
-    curr: f32
+impl Default for Rand {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Rand {
     pub fn new() -> Self {
-        Self {
-            curr: 1.0
-        }
+        Self::new_with_seed(0)
     }
 
-    pub fn new_with_seed(seed: f32) -> Self {
-        Self {
-            curr: seed
-        }
+    pub fn new_with_seed(seed: u64) -> Self {
+        Self::new_with_stream(seed, DEFAULT_STREAM)
     }
 
-    pub fn new_with_nanos() -> Self {
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .subsec_nanos();
-        Self {
-            curr: ((seed as f32 * 19475382.0) % 100000.0) / 1843489234.7342
-        }
+    pub fn new_with_stream(seed: u64, stream: u64) -> Self {
+        // the increment has to be odd
+        let mut randomizer = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        randomizer.next_u32();
+        randomizer.state = randomizer.state.wrapping_add(splitmix64(seed ^ stream));
+        randomizer.next_u32();
+        randomizer
     }
 
-    pub fn next(&mut self) -> f32 {
-        // return random_f32();
-        return rand::random::<f32>();
-        self.curr = self.curr * 1103515245.0 + 12345.0;
-        self.curr = (self.curr / 65536.0) % 32768.0;
-        self.curr / 32768.0
+    // a new generator on its own stream, seeded from this one
+    pub fn split(&mut self, stream: u64) -> Self {
+        let seed = ((self.next_u32() as u64) << 32) | self.next_u32() as u64;
+        Self::new_with_stream(seed, stream)
     }
 
-    pub fn next_with_range(&mut self, range_min: f32, range_max: f32) -> f32 {
-        // let rand_num = random_f32(); 
-        // return rand_num * (range_max - range_min) + range_min;
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
 
-        let rand_num = rand::random::<f32>();
-     
-        return rand_num * (range_max - range_min) + range_min;
-        self.curr = self.curr * 1103515245.0 + 12345.0;
-        self.curr = (self.curr / 65536.0) % 32768.0;
-        let temp = self.curr / 32768.0;
-        temp * (range_max - range_min) + range_min
+    // uniform in [0, 1), built from 53 random bits so every f64 step is reachable
+    pub fn next_f64(&mut self) -> f64 {
+        let bits = ((self.next_u32() as u64) << 21) ^ (self.next_u32() as u64 >> 11);
+        (bits & ((1u64 << 53) - 1)) as f64 * (1.0 / (1u64 << 53) as f64)
     }
 
+    pub fn next_with_range(&mut self, range_min: f64, range_max: f64) -> f64 {
+        self.next_f64() * (range_max - range_min) + range_min
+    }
+}
+
+// scrambles nearby numbers into unrelated ones, see https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
 }
@@ -1,5 +1,6 @@
 use crate::vec3::{Vec3, Point3};
 
+#[derive(Clone, Copy, Debug)]
 pub struct Ray3 {
     origin: Point3,
     direction: Vec3
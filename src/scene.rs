@@ -0,0 +1,189 @@
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::environment::{Environment, Gradient};
+use crate::object::{HitRecord, Hittable};
+use crate::rand::Rand;
+use crate::ray3::Ray3;
+use crate::vec3::{Point3, Vec3};
+
+// the world we render. it gets built once by whoever is driving the camera and is then
+// handed to the camera by reference, so nothing is reallocated per ray
+pub struct Scene {
+    objects: Group,
+    // what rays that miss everything see
+    environment: Box<dyn Environment>,
+}
+
+impl Scene {
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Self {
+        Self {
+            objects: Group::new(objects),
+            environment: Box::new(Gradient::sky()),
+        }
+    }
+
+    pub fn with_environment(mut self, environment: Box<dyn Environment>) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    pub fn environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+
+    pub fn objects(&self) -> &[Box<dyn Hittable>] {
+        self.objects.objects()
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    // indices of the objects that can be sampled as lights
+    pub fn lights(&self) -> &[usize] {
+        &self.objects.lights
+    }
+
+    // lights to pick from, the environment counts as one when it can be sampled
+    fn light_count(&self) -> usize {
+        self.lights().len() + self.environment.is_light() as usize
+    }
+
+    // picks one light uniformly and a direction towards it. the density of that, over all
+    // the lights, is what light_pdf gives back
+    pub fn sample_light(&self, origin: &Point3, randomizer: &mut Rand) -> Option<Vec3> {
+        let count = self.light_count();
+        if count == 0 {
+            return None;
+        }
+        let pick = ((randomizer.next_f64() * count as f64) as usize).min(count - 1);
+        match self.lights().get(pick) {
+            Some(&i) => Some(self.objects()[i].random(origin, randomizer)),
+            None => Some(self.environment.random(randomizer)),
+        }
+    }
+
+    pub fn light_pdf(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let count = self.light_count();
+        if count == 0 {
+            return 0.0;
+        }
+        let mut sum: f64 = self.lights().iter().map(|&i| self.objects()[i].pdf_value(origin, direction)).sum();
+        if self.environment.is_light() {
+            sum += self.environment.pdf_value(direction);
+        }
+        sum / count as f64
+    }
+
+    // of everything in the bvh, planes and other unbounded objects are left out
+    pub fn bounding_box(&self) -> Aabb {
+        self.objects.bvh.bounds()
+    }
+
+    // closest hit with t inside (t_min, t_max)
+    pub fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.objects.hit(ray, t_min, t_max)
+    }
+
+    // same thing as hit but checks every object, handy for checking the bvh against
+    pub fn hit_linear(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest: Option<HitRecord> = None;
+        let mut closest_t = t_max;
+        for object in self.objects() {
+            if let Some(hit) = object.hit(ray, t_min, closest_t) {
+                closest_t = hit.t;
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+}
+
+// a bunch of objects with a bvh of their own, which is itself one object. the scene keeps
+// everything in one of these, and wrapped in an Arc it's how a mesh gets shared by every
+// Instance of it. as a light it's all of its lights together, picked between uniformly
+pub struct Group {
+    objects: Vec<Box<dyn Hittable>>,
+    bvh: Bvh,
+    // which object each primitive of the bvh is
+    bounded: Vec<usize>,
+    // objects without a finite bounding box (planes), tried against every ray
+    unbounded: Vec<usize>,
+    // the ones that can be sampled as lights
+    lights: Vec<usize>,
+}
+
+impl Group {
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> Self {
+        let boxes: Vec<Aabb> = objects.iter().map(|object| object.bounding_box()).collect();
+        let (bounded, unbounded): (Vec<usize>, Vec<usize>) = (0..objects.len()).partition(|&i| boxes[i].is_finite());
+        let bvh = Bvh::build(&bounded.iter().map(|&i| boxes[i]).collect::<Vec<Aabb>>());
+        let lights = (0..objects.len()).filter(|&i| objects[i].is_light()).collect();
+        Self { objects, bvh, bounded, unbounded, lights }
+    }
+
+    pub fn objects(&self) -> &[Box<dyn Hittable>] {
+        &self.objects
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl Hittable for Group {
+    fn hit(&self, ray: &Ray3, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut closest: Option<HitRecord> = None;
+        // the unbounded ones go first, a ground plane hit lets the bvh skip whatever is below it
+        let mut t_max = t_max;
+        for &i in &self.unbounded {
+            if let Some(hit) = self.objects[i].hit(ray, t_min, t_max) {
+                t_max = hit.t;
+                closest = Some(hit);
+            }
+        }
+        self.bvh.traverse(ray, t_min, t_max, |i, closest_t| {
+            let hit = self.objects[self.bounded[i]].hit(ray, t_min, closest_t)?;
+            let t = hit.t;
+            closest = Some(hit);
+            Some(t)
+        });
+        closest
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        if !self.unbounded.is_empty() {
+            let infinity = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+            return Aabb::new(-infinity, infinity);
+        }
+        self.bvh.bounds()
+    }
+
+    fn is_light(&self) -> bool {
+        !self.lights.is_empty()
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self.lights.iter().map(|&i| self.objects[i].pdf_value(origin, direction)).sum();
+        sum / self.lights.len() as f64
+    }
+
+    fn random(&self, origin: &Point3, randomizer: &mut Rand) -> Vec3 {
+        if self.lights.is_empty() {
+            return Vec3::new(1.0, 0.0, 0.0);
+        }
+        let pick = ((randomizer.next_f64() * self.lights.len() as f64) as usize).min(self.lights.len() - 1);
+        self.objects[self.lights[pick]].random(origin, randomizer)
+    }
+}
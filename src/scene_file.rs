@@ -0,0 +1,667 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::environment::{Environment, Gradient, LatLongImage, SolidColor};
+use crate::gltf;
+use crate::hdr;
+use crate::instance::Instance;
+use crate::json::{self, Json, ParseError};
+use crate::mat4::Mat4;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use crate::obj;
+use crate::object::{cuboid, Cone, Cylinder, Disk, Hittable, Plane, Quad, Sphere, Torus, Triangle};
+use crate::ply;
+use crate::scene::{Group, Scene};
+use crate::stl;
+use crate::tonemap::{PostProcess, ToneMap};
+use crate::utils;
+use crate::vec3::{Color3, Point3, Vec3};
+
+// loads scenes written as json, so they can be tweaked without recompiling. a file looks
+// like this (every section is optional except objects):
+//
+// {
+//     "render": { "width": 400, "aspect_ratio": 1.7778, "samples_per_pixel": 100,
+//                 "max_depth": 50, "seed": 0, "threads": 0,
+//                 "tonemap": "aces", "exposure": 0, "white_point": 4 },
+//     "camera": { "look_from": [0, 0, 0], "look_at": [0, 0, -1], "vup": [0, 1, 0], "vfov": 90,
+//                 "defocus_angle": 0, "focus_distance": 1 },
+//     "environment": { "type": "gradient", "bottom": [1, 1, 1], "top": [0.5, 0.7, 1] },
+//     "materials": {
+//         "ground": { "type": "lambertian", "albedo": [0.8, 0.8, 0.0] },
+//         "steel": { "type": "metal", "albedo": [0.8, 0.8, 0.8], "fuzz": 0.1 },
+//         "glass": { "type": "dielectric", "refraction_index": 1.5 },
+//         "lamp": { "type": "diffuse_light", "emit": [4, 4, 4] }
+//     },
+//     "objects": [
+//         { "type": "sphere", "center": [0, -100.5, -1], "radius": 100, "material": "ground" },
+//         { "type": "sphere", "center": [0, 0, -1], "radius": 0.5,
+//           "material": { "type": "lambertian", "albedo": [0.1, 0.2, 0.5] } },
+//         { "type": "triangle", "vertices": [[-1, 0, -2], [1, 0, -2], [0, 1, -2]],
+//           "normals": [[0, 0, 1], [0, 0, 1], [0, 0, 1]], "uvs": [[0, 0], [1, 0], [0.5, 1]],
+//           "material": "steel" },
+//         { "type": "plane", "point": [0, -0.5, 0], "normal": [0, 1, 0], "material": "ground" },
+//         { "type": "quad", "corner": [-1, 2, -2], "u": [2, 0, 0], "v": [0, 0, 1], "material": "lamp" },
+//         { "type": "box", "min": [1, -0.5, -2], "max": [2, 0.5, -1], "material": "steel" },
+//         { "type": "cylinder", "center": [-2, -0.5, -2], "radius": 0.3, "height": 1,
+//           "caps": true, "material": "ground" },
+//         { "type": "torus", "center": [0, 0, -3], "major_radius": 1, "minor_radius": 0.2,
+//           "sweep": 270, "material": "steel" },
+//         { "type": "mesh", "path": "teapot.obj", "groups": ["lid", "body"],
+//           "transform": { "scale": 0.5, "rotate": [0, 45, 0], "translate": [2, -0.5, -3] } }
+//     ]
+// }
+//
+// materials can be referenced by name or written inline. a quad faces the way u x v points,
+// which is the only side it glows from as a light. disks, cylinders and cones ("cone" takes
+// the same keys as "cylinder") stand upright on their center, a torus lies flat around it,
+// and all of them can be cut down to a wedge of "sweep" degrees. meshes are .obj, .ply, .stl,
+// .gltf or .glb files relative to the scene file, a "material" on a mesh replaces the ones
+// that came with it and "groups" keeps only the named obj groups or gltf nodes. gltf lights
+// come along, its cameras don't (a .gltf or .glb can be loaded as the whole scene for that).
+// any object can have a "transform", one step or a list of them applied first to last. a
+// step scales (by one number or one per axis), rotates (degrees around x, then y, then z)
+// and translates, in that order, or is a "matrix" of four rows on its own. a mesh placed
+// more than once with a transform is only loaded once and shared between all of them.
+// the environment is what rays that miss everything see, the sky above by default. besides
+// "gradient" (which also takes an optional "up") there's
+// { "type": "solid", "color": [0, 0, 0] } and
+// { "type": "image", "path": "sky.hdr", "intensity": 1, "rotation": 0 } for a lat-long
+// panorama (.hdr, .pfm or .ppm, relative to the scene file) turned by rotation degrees
+// around +y, which also lights the scene. anything malformed comes back as an error
+// pointing at the line and column of the offending value
+
+pub struct SceneFile {
+    pub camera: Camera,
+    pub scene: Scene,
+    pub post_process: PostProcess,
+}
+
+// a .gltf or .glb is taken as a whole scene, see `from_gltf`
+pub fn load(path: &str) -> io::Result<SceneFile> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("");
+    if matches!(extension.to_ascii_lowercase().as_str(), "gltf" | "glb") {
+        let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.8, 0.8, 0.8)));
+        return gltf::load(path, default_material).map(from_gltf);
+    }
+    let text = fs::read_to_string(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    parse_with_directory(&text, directory)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}", path, e)))
+}
+
+// renders through the first camera in the file. without one, the camera looks down -z at
+// the whole model from far enough back to fit it in
+pub fn from_gltf(gltf: gltf::Gltf) -> SceneFile {
+    let aspect_ratio = 16.0 / 9.0;
+    let image_width = 400;
+    let camera = match gltf.cameras.first() {
+        Some(camera) => camera.to_camera(aspect_ratio, image_width),
+        None => {
+            let (center, radius) = if gltf.mesh.positions.is_empty() {
+                (Point3::new(0.0, 0.0, 0.0), 1.0)
+            } else {
+                let bounds = gltf.mesh.bounding_box();
+                (bounds.centroid(), (0.5 * bounds.extent().length()).max(1e-3))
+            };
+            let vfov: f64 = 40.0;
+            let distance = radius / (vfov / 2.0).to_radians().sin();
+            Camera::new(
+                aspect_ratio,
+                image_width,
+                center + Vec3::new(0.0, 0.0, distance),
+                center,
+                Vec3::new(0.0, 1.0, 0.0),
+                vfov,
+            )
+        }
+    };
+    SceneFile {
+        camera,
+        scene: Scene::new(gltf.into_hittables()),
+        post_process: PostProcess::default(),
+    }
+}
+
+// files the scene refers to are looked up relative to the working directory
+pub fn parse(text: &str) -> Result<SceneFile, ParseError> {
+    parse_with_directory(text, Path::new(""))
+}
+
+// same as parse, with files the scene refers to looked up relative to `directory`
+pub fn parse_with_directory(text: &str, directory: &Path) -> Result<SceneFile, ParseError> {
+    let root = json::parse(text)?;
+    check_keys(&root, &["render", "camera", "environment", "materials", "objects"])?;
+
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    if let Some(section) = root.get("materials") {
+        for (name, material) in section.as_object()? {
+            materials.insert(name.clone(), parse_material(material)?);
+        }
+    }
+
+    let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+    let mut meshes: HashMap<MeshKey, Arc<Group>> = HashMap::new();
+    for object in field(&root, "objects")?.as_array()? {
+        objects.extend(parse_placed_object(object, &materials, directory, &mut meshes)?);
+    }
+
+    let camera = parse_camera(root.get("camera"), root.get("render"))?;
+    let post_process = match root.get("render") {
+        Some(render) => parse_post_process(render)?,
+        None => PostProcess::default(),
+    };
+    let mut scene = Scene::new(objects);
+    if let Some(environment) = root.get("environment") {
+        scene = scene.with_environment(parse_environment(environment, directory)?);
+    }
+    Ok(SceneFile {
+        camera,
+        scene,
+        post_process,
+    })
+}
+
+fn parse_environment(json: &Json, directory: &Path) -> Result<Box<dyn Environment>, ParseError> {
+    let kind = field(json, "type")?;
+    match kind.as_str()? {
+        "solid" => {
+            check_keys(json, &["type", "color"])?;
+            Ok(Box::new(SolidColor::new(vec3(field(json, "color")?)?)))
+        }
+        "gradient" => {
+            check_keys(json, &["type", "bottom", "top", "up"])?;
+            let sky = Gradient::sky();
+            let up = optional_vec3(json, "up", sky.up)?;
+            if up.near_zero() {
+                return Err(field(json, "up")?.error("\"up\" can't be a zero vector"));
+            }
+            Ok(Box::new(Gradient::new(
+                optional_vec3(json, "bottom", sky.bottom)?,
+                optional_vec3(json, "top", sky.top)?,
+                up,
+            )))
+        }
+        "image" => {
+            check_keys(json, &["type", "path", "intensity", "rotation"])?;
+            let path_json = field(json, "path")?;
+            let path = directory.join(path_json.as_str()?);
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            let image = match extension.to_ascii_lowercase().as_str() {
+                "hdr" => hdr::read_hdr(&path.to_string_lossy()),
+                "pfm" => hdr::read_pfm(&path.to_string_lossy()),
+                "ppm" => utils::read_ppm(&path.to_string_lossy()),
+                _ => return Err(path_json.error("environment images have to be .hdr, .pfm or .ppm")),
+            }
+            .map_err(|e| path_json.error(format!("{}: {}", path.display(), e)))?;
+            let intensity = optional_f64(json, "intensity", 1.0)?;
+            if intensity < 0.0 {
+                return Err(field(json, "intensity")?.error("\"intensity\" can't be negative"));
+            }
+            let rotation = optional_f64(json, "rotation", 0.0)?;
+            Ok(Box::new(LatLongImage::new(image, intensity).with_rotation(rotation)))
+        }
+        other => Err(kind.error(format!("unknown environment type \"{}\"", other))),
+    }
+}
+
+// exposure and tone mapping out of the render section, keys were checked by parse_camera
+fn parse_post_process(render: &Json) -> Result<PostProcess, ParseError> {
+    let mut post_process = PostProcess::default();
+    if let Some(value) = render.get("tonemap") {
+        let name = value.as_str()?;
+        post_process.tone_map = ToneMap::from_name(name).ok_or_else(|| {
+            value.error(format!(
+                "unknown tonemap \"{}\" (expected one of: {})",
+                name,
+                ToneMap::names().join(", ")
+            ))
+        })?;
+    }
+    if let Some(value) = render.get("white_point") {
+        let ToneMap::ExtendedReinhard { .. } = post_process.tone_map else {
+            return Err(value.error("\"white_point\" only applies to the extended-reinhard tonemap"));
+        };
+        post_process.tone_map = ToneMap::ExtendedReinhard { white: positive(render, "white_point", 4.0)? };
+    }
+    post_process.exposure = optional_f64(render, "exposure", 0.0)?;
+    Ok(post_process)
+}
+
+fn parse_camera(camera: Option<&Json>, render: Option<&Json>) -> Result<Camera, ParseError> {
+    let mut aspect_ratio = 16.0 / 9.0;
+    let mut image_width = 400;
+    let mut threads = 0;
+    let mut seed = 0;
+    let mut samples_per_pixel = None;
+    let mut max_depth = None;
+    if let Some(render) = render {
+        check_keys(
+            render,
+            &[
+                "width", "aspect_ratio", "samples_per_pixel", "max_depth", "threads", "seed",
+                "tonemap", "exposure", "white_point",
+            ],
+        )?;
+        aspect_ratio = positive(render, "aspect_ratio", aspect_ratio)?;
+        let width = optional_u64(render, "width", image_width as u64)?;
+        if width == 0 || width > u32::MAX as u64 {
+            return Err(field(render, "width")?.error(format!("{} isn't a usable image width", width)));
+        }
+        image_width = width as u32;
+        threads = optional_u64(render, "threads", threads as u64)? as usize;
+        seed = optional_u64(render, "seed", seed)?;
+        if let Some(value) = render.get("samples_per_pixel") {
+            let n = value.as_u64()?;
+            if n == 0 || n > u32::MAX as u64 {
+                return Err(value.error("\"samples_per_pixel\" has to be at least 1"));
+            }
+            samples_per_pixel = Some(n as u32);
+        }
+        if let Some(value) = render.get("max_depth") {
+            max_depth = Some(value.as_u64()?.min(u32::MAX as u64) as u32);
+        }
+    }
+
+    let mut look_from = Point3::new(0.0, 0.0, 0.0);
+    let mut look_at = Point3::new(0.0, 0.0, -1.0);
+    let mut vup = Vec3::new(0.0, 1.0, 0.0);
+    let mut vfov = 90.0;
+    let mut defocus = None;
+    if let Some(camera) = camera {
+        check_keys(camera, &["look_from", "look_at", "vup", "vfov", "defocus_angle", "focus_distance"])?;
+        look_from = optional_vec3(camera, "look_from", look_from)?;
+        look_at = optional_vec3(camera, "look_at", look_at)?;
+        vup = optional_vec3(camera, "vup", vup)?;
+        vfov = positive(camera, "vfov", vfov)?;
+        if vfov >= 180.0 {
+            return Err(field(camera, "vfov")?.error("\"vfov\" has to be less than 180 degrees"));
+        }
+        if (look_from - look_at).near_zero() {
+            return Err(camera.error("\"look_from\" and \"look_at\" can't be the same point"));
+        }
+        if vup.cross(&(look_from - look_at)).near_zero() {
+            return Err(camera.error("\"vup\" can't point along the viewing direction"));
+        }
+        // focus on look_at unless told otherwise
+        let defocus_angle = optional_f64(camera, "defocus_angle", 0.0)?;
+        if !(0.0..180.0).contains(&defocus_angle) {
+            return Err(field(camera, "defocus_angle")?.error("\"defocus_angle\" has to be in [0, 180)"));
+        }
+        let focus_distance = positive(camera, "focus_distance", (look_from - look_at).length())?;
+        defocus = Some((defocus_angle, focus_distance));
+    }
+
+    let mut view_camera = Camera::new(aspect_ratio, image_width, look_from, look_at, vup, vfov)
+        .with_threads(threads)
+        .with_seed(seed);
+    if let Some((defocus_angle, focus_distance)) = defocus {
+        view_camera = view_camera.with_defocus(defocus_angle, focus_distance);
+    }
+    if let Some(samples_per_pixel) = samples_per_pixel {
+        view_camera = view_camera.with_samples_per_pixel(samples_per_pixel);
+    }
+    if let Some(max_depth) = max_depth {
+        view_camera = view_camera.with_max_depth(max_depth);
+    }
+    Ok(view_camera)
+}
+
+fn parse_material(json: &Json) -> Result<Arc<dyn Material>, ParseError> {
+    let kind = field(json, "type")?;
+    match kind.as_str()? {
+        "lambertian" => {
+            check_keys(json, &["type", "albedo"])?;
+            Ok(Arc::new(Lambertian::new(vec3(field(json, "albedo")?)?)))
+        }
+        "metal" => {
+            check_keys(json, &["type", "albedo", "fuzz"])?;
+            Ok(Arc::new(Metal::new(
+                vec3(field(json, "albedo")?)?,
+                optional_f64(json, "fuzz", 0.0)?,
+            )))
+        }
+        "dielectric" => {
+            check_keys(json, &["type", "albedo", "refraction_index"])?;
+            Ok(Arc::new(Dielectric::new(
+                optional_vec3(json, "albedo", Color3::new(1.0, 1.0, 1.0))?,
+                positive(json, "refraction_index", 1.5)?,
+            )))
+        }
+        "diffuse_light" => {
+            check_keys(json, &["type", "emit"])?;
+            let emit = vec3(field(json, "emit")?)?;
+            if emit.x() < 0.0 || emit.y() < 0.0 || emit.z() < 0.0 {
+                return Err(field(json, "emit")?.error("\"emit\" can't be negative"));
+            }
+            Ok(Arc::new(DiffuseLight::new(emit)))
+        }
+        other => Err(kind.error(format!("unknown material type \"{}\"", other))),
+    }
+}
+
+// either the name of something in "materials" or a material written inline
+fn material_ref(
+    json: &Json,
+    materials: &HashMap<String, Arc<dyn Material>>,
+) -> Result<Arc<dyn Material>, ParseError> {
+    let material = field(json, "material")?;
+    match material.as_str() {
+        Ok(name) => materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| material.error(format!("no material named \"{}\"", name))),
+        Err(_) => parse_material(material),
+    }
+}
+
+// what makes two placed meshes the same one: the path, the groups kept and the name of the
+// material they got (if any)
+type MeshKey = (String, Option<Vec<String>>, Option<String>);
+
+// an object with an optional "transform" on top. a transformed object turns into a single
+// Instance of its geometry, and a transformed mesh is loaded once however many times it's
+// placed (unless it has an inline material, which makes each one its own)
+fn parse_placed_object(
+    json: &Json,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    directory: &Path,
+    meshes: &mut HashMap<MeshKey, Arc<Group>>,
+) -> Result<Vec<Box<dyn Hittable>>, ParseError> {
+    let Some(transform_json) = json.get("transform") else {
+        return parse_object(json, materials, directory);
+    };
+    let transform = parse_transform(transform_json)?;
+    // an inline material is a new one every time, so those meshes don't get shared
+    let key = match field(json, "type")?.as_str()? {
+        "mesh" if json.get("material").is_none_or(|material| material.as_str().is_ok()) => {
+            let path = field(json, "path")?.as_str()?.to_string();
+            let groups = match json.get("groups") {
+                Some(groups) => {
+                    let names = groups.as_array()?.iter().map(|name| Ok(name.as_str()?.to_string()));
+                    Some(names.collect::<Result<_, ParseError>>()?)
+                }
+                None => None,
+            };
+            let material = json.get("material").map(|material| material.as_str()).transpose()?.map(str::to_string);
+            Some((path, groups, material))
+        }
+        _ => None,
+    };
+    let geometry: Arc<dyn Hittable> = match key.as_ref().and_then(|key| meshes.get(key)) {
+        Some(group) => group.clone(),
+        None => {
+            let mut objects = parse_object(json, materials, directory)?;
+            if key.is_none() && objects.len() == 1 {
+                Arc::from(objects.remove(0))
+            } else {
+                let group = Arc::new(Group::new(objects));
+                if let Some(key) = key {
+                    meshes.insert(key, group.clone());
+                }
+                group
+            }
+        }
+    };
+    let instance = Instance::new(geometry, transform)
+        .ok_or_else(|| transform_json.error("the transform flattens things and can't be undone"))?;
+    Ok(vec![Box::new(instance)])
+}
+
+// most objects are one hittable, meshes are one per triangle
+fn parse_object(
+    json: &Json,
+    materials: &HashMap<String, Arc<dyn Material>>,
+    directory: &Path,
+) -> Result<Vec<Box<dyn Hittable>>, ParseError> {
+    let kind = field(json, "type")?;
+    match kind.as_str()? {
+        "sphere" => {
+            check_keys(json, &["type", "center", "radius", "material", "transform"])?;
+            Ok(vec![Box::new(Sphere::new(
+                vec3(field(json, "center")?)?,
+                positive(json, "radius", 1.0)?,
+                material_ref(json, materials)?,
+            ))])
+        }
+        "triangle" => {
+            check_keys(json, &["type", "vertices", "normals", "uvs", "material", "transform"])?;
+            let vertices = three(field(json, "vertices")?, vec3)?;
+            let mut triangle = Triangle::new(vertices[0], vertices[1], vertices[2], material_ref(json, materials)?);
+            if let Some(normals) = json.get("normals") {
+                let normals = three(normals, vec3)?;
+                triangle = triangle.with_normals(normals);
+            }
+            if let Some(uvs) = json.get("uvs") {
+                let uvs = three(uvs, |uv| {
+                    let items = uv.as_array()?;
+                    if items.len() != 2 {
+                        return Err(uv.error(format!("expected 2 numbers, found {}", items.len())));
+                    }
+                    Ok((items[0].as_f64()?, items[1].as_f64()?))
+                })?;
+                triangle = triangle.with_uvs(uvs);
+            }
+            Ok(vec![Box::new(triangle)])
+        }
+        "plane" => {
+            check_keys(json, &["type", "point", "normal", "material", "transform"])?;
+            let normal_json = field(json, "normal")?;
+            let normal = vec3(normal_json)?;
+            if normal.near_zero() {
+                return Err(normal_json.error("\"normal\" can't be zero"));
+            }
+            Ok(vec![Box::new(Plane::new(vec3(field(json, "point")?)?, normal, material_ref(json, materials)?))])
+        }
+        "quad" => {
+            check_keys(json, &["type", "corner", "u", "v", "material", "transform"])?;
+            let u = vec3(field(json, "u")?)?;
+            let v = vec3(field(json, "v")?)?;
+            if u.cross(&v).near_zero() {
+                return Err(json.error("\"u\" and \"v\" can't be zero or parallel"));
+            }
+            Ok(vec![Box::new(Quad::new(vec3(field(json, "corner")?)?, u, v, material_ref(json, materials)?))])
+        }
+        "box" => {
+            check_keys(json, &["type", "min", "max", "material", "transform"])?;
+            Ok(cuboid(vec3(field(json, "min")?)?, vec3(field(json, "max")?)?, material_ref(json, materials)?))
+        }
+        "disk" => {
+            check_keys(json, &["type", "center", "radius", "inner_radius", "sweep", "material", "transform"])?;
+            let radius = positive(json, "radius", 1.0)?;
+            let inner_radius = optional_f64(json, "inner_radius", 0.0)?;
+            if !(0.0..radius).contains(&inner_radius) {
+                return Err(field(json, "inner_radius")?.error("\"inner_radius\" has to be in [0, radius)"));
+            }
+            let disk = Disk::new(vec3(field(json, "center")?)?, radius, material_ref(json, materials)?)
+                .with_inner_radius(inner_radius)
+                .with_sweep(sweep(json)?);
+            Ok(vec![Box::new(disk)])
+        }
+        "cylinder" | "cone" => {
+            check_keys(json, &["type", "center", "radius", "height", "caps", "sweep", "material", "transform"])?;
+            let center = vec3(field(json, "center")?)?;
+            let radius = positive(json, "radius", 1.0)?;
+            let height = positive(json, "height", 1.0)?;
+            let capped = json.get("caps").map_or(Ok(false), |caps| caps.as_bool())?;
+            let material = material_ref(json, materials)?;
+            let sweep = sweep(json)?;
+            Ok(vec![if kind.as_str()? == "cylinder" {
+                Box::new(Cylinder::new(center, radius, height, material).with_caps(capped).with_sweep(sweep))
+            } else {
+                Box::new(Cone::new(center, radius, height, material).with_caps(capped).with_sweep(sweep))
+            }])
+        }
+        "torus" => {
+            check_keys(json, &["type", "center", "major_radius", "minor_radius", "sweep", "material", "transform"])?;
+            let major_radius = positive(json, "major_radius", 1.0)?;
+            let minor_radius = positive(json, "minor_radius", 0.25)?;
+            let torus = Torus::new(vec3(field(json, "center")?)?, major_radius, minor_radius, material_ref(json, materials)?)
+                .with_sweep(sweep(json)?);
+            Ok(vec![Box::new(torus)])
+        }
+        "mesh" => {
+            check_keys(json, &["type", "path", "material", "groups", "transform"])?;
+            let path_json = field(json, "path")?;
+            let path = directory.join(path_json.as_str()?);
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+            // the material given here replaces whatever the file says
+            let material = match json.get("material") {
+                Some(_) => Some(material_ref(json, materials)?),
+                None => None,
+            };
+            let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(Color3::new(0.8, 0.8, 0.8)));
+            let path = path.to_string_lossy();
+            // gltf files can bring lights along, every other format is just the mesh
+            let (mut mesh, lights) = match extension.to_ascii_lowercase().as_str() {
+                "obj" => obj::load(&path, default_material).map(|mesh| (mesh, Vec::new())),
+                "ply" => ply::load(&path, default_material).map(|mesh| (mesh, Vec::new())),
+                "stl" => stl::load(&path, default_material).map(|mesh| (mesh, Vec::new())),
+                "gltf" | "glb" => gltf::load(&path, default_material).map(|gltf| (gltf.mesh, gltf.lights)),
+                _ => return Err(path_json.error("meshes have to be .obj, .ply, .stl, .gltf or .glb")),
+            }
+            .map_err(|e| path_json.error(e.to_string()))?;
+            if let Some(groups) = json.get("groups") {
+                let names = groups.as_array()?.iter().map(|name| name.as_str()).collect::<Result<Vec<_>, _>>()?;
+                mesh.retain_groups(&names);
+            }
+            if let Some(material) = material {
+                mesh.set_material(material);
+            }
+            let mut objects = mesh.into_hittables();
+            objects.extend(lights);
+            Ok(objects)
+        }
+        other => Err(kind.error(format!("unknown object type \"{}\"", other))),
+    }
+}
+
+// one step or a list of them, applied first to last. a step can scale (by one number or one
+// per axis), rotate (degrees around x, then y, then z) and translate, in that order, or be
+// a "matrix" of four rows by itself
+fn parse_transform(json: &Json) -> Result<Mat4, ParseError> {
+    let steps = match json.as_array() {
+        Ok(steps) => steps,
+        Err(_) => std::slice::from_ref(json),
+    };
+    let mut transform = Mat4::IDENTITY;
+    for step in steps {
+        check_keys(step, &["scale", "rotate", "translate", "matrix"])?;
+        if let Some(matrix) = step.get("matrix") {
+            if step.as_object()?.len() > 1 {
+                return Err(step.error("\"matrix\" goes in a step of its own"));
+            }
+            let rows = matrix.as_array()?;
+            if rows.len() != 4 {
+                return Err(matrix.error(format!("expected 4 rows, found {}", rows.len())));
+            }
+            let mut values = [[0.0; 4]; 4];
+            for (row, json) in values.iter_mut().zip(rows) {
+                let items = json.as_array()?;
+                if items.len() != 4 {
+                    return Err(json.error(format!("expected 4 numbers, found {}", items.len())));
+                }
+                for (value, item) in row.iter_mut().zip(items) {
+                    *value = item.as_f64()?;
+                }
+            }
+            if values[3] != [0.0, 0.0, 0.0, 1.0] {
+                return Err(rows[3].error("the last row has to be [0, 0, 0, 1], there's no perspective here"));
+            }
+            transform = Mat4::from_rows(values) * transform;
+            continue;
+        }
+        if let Some(scale) = step.get("scale") {
+            let factors = match scale.as_f64() {
+                Ok(factor) => Vec3::new(factor, factor, factor),
+                Err(_) => vec3(scale)?,
+            };
+            transform = Mat4::scaling(factors) * transform;
+        }
+        if let Some(rotate) = step.get("rotate") {
+            let degrees = vec3(rotate)?;
+            for axis in 0..3 {
+                let mut around = Vec3::new(0.0, 0.0, 0.0);
+                around[axis] = 1.0;
+                transform = Mat4::rotation(around, degrees[axis]) * transform;
+            }
+        }
+        if let Some(translate) = step.get("translate") {
+            transform = Mat4::translation(vec3(translate)?) * transform;
+        }
+    }
+    Ok(transform)
+}
+
+// catches typos like "raduis" instead of quietly ignoring them
+fn check_keys(json: &Json, allowed: &[&str]) -> Result<(), ParseError> {
+    for (key, value) in json.as_object()? {
+        if !allowed.contains(&key.as_str()) {
+            return Err(value.error(format!(
+                "unknown key \"{}\" (expected one of: {})",
+                key,
+                allowed.join(", ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn field<'a>(json: &'a Json, key: &str) -> Result<&'a Json, ParseError> {
+    json.get(key)
+        .ok_or_else(|| json.error(format!("missing \"{}\"", key)))
+}
+
+fn vec3(json: &Json) -> Result<Vec3, ParseError> {
+    let items = json.as_array()?;
+    if items.len() != 3 {
+        return Err(json.error(format!("expected 3 numbers, found {}", items.len())));
+    }
+    Ok(Vec3::new(items[0].as_f64()?, items[1].as_f64()?, items[2].as_f64()?))
+}
+
+// an array of exactly three things, one per triangle corner
+fn three<T>(json: &Json, item: impl Fn(&Json) -> Result<T, ParseError>) -> Result<[T; 3], ParseError> {
+    let items = json.as_array()?;
+    if items.len() != 3 {
+        return Err(json.error(format!("expected 3 entries, found {}", items.len())));
+    }
+    Ok([item(&items[0])?, item(&items[1])?, item(&items[2])?])
+}
+
+fn optional_vec3(json: &Json, key: &str, default: Vec3) -> Result<Vec3, ParseError> {
+    json.get(key).map_or(Ok(default), vec3)
+}
+
+fn optional_f64(json: &Json, key: &str, default: f64) -> Result<f64, ParseError> {
+    json.get(key).map_or(Ok(default), |value| value.as_f64())
+}
+
+fn optional_u64(json: &Json, key: &str, default: u64) -> Result<u64, ParseError> {
+    json.get(key).map_or(Ok(default), |value| value.as_u64())
+}
+
+// optional number that has to be > 0
+fn positive(json: &Json, key: &str, default: f64) -> Result<f64, ParseError> {
+    let Some(value) = json.get(key) else {
+        return Ok(default);
+    };
+    let n = value.as_f64()?;
+    if n <= 0.0 {
+        return Err(value.error(format!("\"{}\" has to be greater than 0", key)));
+    }
+    Ok(n)
+}
+
+// how much of the way around a disk, cylinder, cone or torus goes, in degrees
+fn sweep(json: &Json) -> Result<f64, ParseError> {
+    let degrees = optional_f64(json, "sweep", 360.0)?;
+    if degrees <= 0.0 || degrees > 360.0 {
+        return Err(field(json, "sweep")?.error("\"sweep\" has to be in (0, 360]"));
+    }
+    Ok(degrees)
+}
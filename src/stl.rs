@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::mesh::{Face, Mesh};
+use crate::vec3::Point3;
+
+// stereolithography .stl loader, ascii and binary. stl is a plain list of triangles, so
+// corners that sit on exactly the same spot get welded back together into shared vertices.
+// the facet normals are ignored, the winding says which side is the front just as well and
+// plenty of exporters write zeros there anyway
+
+fn invalid_stl(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("not a usable stl: {}", message))
+}
+
+// every face gets `material`
+pub fn load(path: &str, material: Arc<dyn Material>) -> io::Result<Mesh> {
+    let data = fs::read(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    parse(&data, material).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+pub fn parse(data: &[u8], material: Arc<dyn Material>) -> io::Result<Mesh> {
+    let triangles = if is_binary(data) { parse_binary(data)? } else { parse_ascii(data)? };
+
+    let mut mesh = Mesh::new();
+    mesh.materials.push(material);
+    mesh.groups.push("default".to_string());
+    // keyed on the bits, -0.0 and 0.0 are close enough to not care
+    let mut welded: HashMap<[u64; 3], usize> = HashMap::new();
+    for triangle in triangles {
+        let positions = triangle.map(|point| {
+            let key = [point.x().to_bits(), point.y().to_bits(), point.z().to_bits()];
+            *welded.entry(key).or_insert_with(|| {
+                mesh.positions.push(point);
+                mesh.positions.len() - 1
+            })
+        });
+        mesh.faces.push(Face { positions, normals: None, uvs: None, material: 0, group: 0 });
+    }
+    Ok(mesh)
+}
+
+// binary files are supposed to not start with "solid" but plenty do, so the size has the
+// final say: an 80 byte header, a triangle count and 50 bytes per triangle
+fn is_binary(data: &[u8]) -> bool {
+    if !data.starts_with(b"solid") {
+        return true;
+    }
+    match data.get(80..84) {
+        Some(count) => {
+            let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+            data.len() == 84 + 50 * count
+        }
+        None => false,
+    }
+}
+
+fn parse_binary(data: &[u8]) -> io::Result<Vec<[Point3; 3]>> {
+    let count = data.get(80..84).ok_or_else(|| invalid_stl("header ends early"))?;
+    let count = u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize;
+    let expected = 84 + 50 * count;
+    if data.len() < expected {
+        return Err(invalid_stl(&format!(
+            "file is truncated, {} triangles need {} bytes but there are only {}",
+            count,
+            expected,
+            data.len()
+        )));
+    }
+    let number = |offset: usize| -> f64 {
+        f32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]) as f64
+    };
+    let mut triangles = Vec::with_capacity(count);
+    for i in 0..count {
+        // 12 bytes of normal, three corners, then 2 bytes of attributes nobody agrees on
+        let start = 84 + 50 * i + 12;
+        let corner = |c: usize| {
+            let offset = start + 12 * c;
+            Point3::new(number(offset), number(offset + 4), number(offset + 8))
+        };
+        let triangle = [corner(0), corner(1), corner(2)];
+        if triangle.iter().any(|point| !(point.x().is_finite() && point.y().is_finite() && point.z().is_finite())) {
+            return Err(invalid_stl(&format!("triangle {} has a corner that isn't a finite number", i)));
+        }
+        triangles.push(triangle);
+    }
+    Ok(triangles)
+}
+
+// solid name / facet normal ... / outer loop / vertex x y z (three times) / endloop /
+// endfacet / ... / endsolid name. errors point at the line
+fn parse_ascii(data: &[u8]) -> io::Result<Vec<[Point3; 3]>> {
+    let text = String::from_utf8_lossy(data);
+    let mut words = Words::new(&text);
+
+    words.expect("solid")?;
+    let mut triangles = Vec::new();
+    // the name is optional and can be more than one word, skip to the first facet
+    let mut word = words.next("facet or endsolid")?;
+    while word != "facet" && word != "endsolid" {
+        word = words.next("facet or endsolid")?;
+    }
+    while word == "facet" {
+        words.expect("normal")?;
+        for _ in 0..3 {
+            words.next("a normal component")?;
+        }
+        words.expect("outer")?;
+        words.expect("loop")?;
+        let mut triangle = [Point3::new(0.0, 0.0, 0.0); 3];
+        for corner in triangle.iter_mut() {
+            words.expect("vertex")?;
+            for axis in 0..3 {
+                corner[axis] = words.number()?;
+            }
+        }
+        words.expect("endloop")?;
+        words.expect("endfacet")?;
+        triangles.push(triangle);
+        word = words.next("facet or endsolid")?;
+    }
+    if word != "endsolid" {
+        return Err(words.error(&format!("expected facet or endsolid, found \"{}\"", word)));
+    }
+    Ok(triangles)
+}
+
+// the whitespace separated words of an ascii file, remembering which line the last one
+// was on
+struct Words<'a> {
+    words: Box<dyn Iterator<Item = (usize, &'a str)> + 'a>,
+    line: usize,
+}
+
+impl<'a> Words<'a> {
+    fn new(text: &'a str) -> Self {
+        let words = text
+            .lines()
+            .enumerate()
+            .flat_map(|(index, line)| line.split_whitespace().map(move |word| (index + 1, word)));
+        Self { words: Box::new(words), line: 1 }
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        invalid_stl(&format!("line {}: {}", self.line, message))
+    }
+
+    fn next(&mut self, expected: &str) -> io::Result<&'a str> {
+        let (line, word) = self
+            .words
+            .next()
+            .ok_or_else(|| invalid_stl(&format!("file is truncated, expected {}", expected)))?;
+        self.line = line;
+        Ok(word)
+    }
+
+    fn expect(&mut self, keyword: &str) -> io::Result<()> {
+        let found = self.next(keyword)?;
+        if found != keyword {
+            return Err(self.error(&format!("expected {}, found \"{}\"", keyword, found)));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> io::Result<f64> {
+        let word = self.next("a number")?;
+        match word.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(self.error(&format!("\"{}\" isn't a finite number", word))),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::vec3::{Color3, Vec3};
+
+// turns the linear radiance the camera produces into display values for the 8/16 bit
+// formats: exposure, then a tone mapping operator to squash everything into [0, 1], then
+// the srgb transfer curve
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMap {
+    // no curve, anything over 1 just clips
+    Clamp,
+    Reinhard,
+    // reinhard that maps `white` (and anything brighter) to 1 instead of only reaching it
+    // at infinity
+    ExtendedReinhard { white: f64 },
+    // stephen hill's fit of the aces rrt + odt
+    Aces,
+    // troy sobotka's agx, using the usual polynomial fit of the default look
+    Agx,
+}
+
+impl ToneMap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "clamp" | "none" => Some(ToneMap::Clamp),
+            "reinhard" => Some(ToneMap::Reinhard),
+            "extended-reinhard" | "extended_reinhard" => Some(ToneMap::ExtendedReinhard { white: 4.0 }),
+            "aces" => Some(ToneMap::Aces),
+            "agx" => Some(ToneMap::Agx),
+            _ => None,
+        }
+    }
+
+    pub fn names() -> &'static [&'static str] {
+        &["clamp", "reinhard", "extended-reinhard", "aces", "agx"]
+    }
+
+    // linear in, linear out, roughly within [0, 1]
+    pub fn apply(&self, color: Color3) -> Color3 {
+        match *self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard { white } => {
+                let white_squared = white * white;
+                scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            }
+            ToneMap::Aces => aces(color),
+            ToneMap::Agx => agx(color),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostProcess {
+    // in stops, every +1 doubles the brightness
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+        }
+    }
+}
+
+impl PostProcess {
+    pub fn new(exposure: f64, tone_map: ToneMap) -> Self {
+        Self { exposure, tone_map }
+    }
+
+    // linear radiance to srgb encoded values in [0, 1]
+    pub fn apply(&self, linear: Color3) -> Color3 {
+        let exposed = linear * 2f64.powf(self.exposure);
+        let mapped = self.tone_map.apply(exposed);
+        Color3::new(
+            srgb_oetf(mapped.x()),
+            srgb_oetf(mapped.y()),
+            srgb_oetf(mapped.z()),
+        )
+    }
+}
+
+// the srgb opto-electronic transfer function (linear -> encoded), clamped to [0, 1]
+pub fn srgb_oetf(linear: f64) -> f64 {
+    // also catches nan
+    if linear.is_nan() || linear <= 0.0 {
+        return 0.0;
+    }
+    if linear >= 1.0 {
+        return 1.0;
+    }
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// the other way, srgb encoded values in [0, 1] back to linear
+pub fn srgb_inverse_oetf(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// rec 709 / srgb luminance
+pub fn luminance(color: Color3) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+// runs the curve on luminance and scales the color to match, keeps hues from shifting
+fn scale_luminance(color: Color3, curve: impl Fn(f64) -> f64) -> Color3 {
+    let l = luminance(color);
+    if l <= 0.0 {
+        return Color3::new(0.0, 0.0, 0.0);
+    }
+    color * (curve(l) / l)
+}
+
+fn mul_matrix(m: &[[f64; 3]; 3], v: Vec3) -> Vec3 {
+    Vec3::new(
+        m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+        m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+        m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+    )
+}
+
+fn map_channels(v: Vec3, f: impl Fn(f64) -> f64) -> Vec3 {
+    Vec3::new(f(v.x()), f(v.y()), f(v.z()))
+}
+
+// srgb -> aces ap1 with the rrt saturation baked in
+const ACES_INPUT: [[f64; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+// odt saturation -> xyz -> d60 to d65 -> srgb
+const ACES_OUTPUT: [[f64; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+fn aces(color: Color3) -> Color3 {
+    let v = mul_matrix(&ACES_INPUT, color);
+    let v = map_channels(v, |x| {
+        let a = x * (x + 0.0245786) - 0.000090537;
+        let b = x * (0.983729 * x + 0.4329510) + 0.238081;
+        a / b
+    });
+    map_channels(mul_matrix(&ACES_OUTPUT, v), |x| x.clamp(0.0, 1.0))
+}
+
+const AGX_INSET: [[f64; 3]; 3] = [
+    [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+    [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+    [0.0423756549057051, 0.0784336, 0.879142973793104],
+];
+const AGX_OUTSET: [[f64; 3]; 3] = [
+    [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+    [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+    [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+];
+// the log encoding covers this many stops around middle grey
+const AGX_MIN_EV: f64 = -12.47393;
+const AGX_MAX_EV: f64 = 4.026069;
+
+fn agx(color: Color3) -> Color3 {
+    let v = mul_matrix(&AGX_INSET, color);
+    let v = map_channels(v, |x| {
+        let ev = x.max(1e-10).log2().clamp(AGX_MIN_EV, AGX_MAX_EV);
+        let x = (ev - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+        // sigmoid fit, out comes a display encoded value
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    });
+    // back to linear so the srgb curve can go on like for every other operator
+    let v = mul_matrix(&AGX_OUTSET, v);
+    map_channels(v, |x| x.max(0.0).powf(2.2))
+}
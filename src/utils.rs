@@ -1,31 +1,31 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Write};
+use crate::exr::{self, Compression};
+use crate::hdr;
+use crate::png::{self, BitDepth};
+use crate::tonemap::{srgb_inverse_oetf, PostProcess};
 use crate::vec3::Vec3;
-use std::time::{SystemTime, UNIX_EPOCH};
 
-// convert from linear space colors to gamma space colors
-fn linear_to_gamma(
-    linear_value: f64
-) -> f64 {
-    if linear_value > 0.0 { // this should ALWAYS be true
-        return linear_value.sqrt();
-    }
-    return 0.0;
-}
-
-pub fn write_to_ppm(
-    filename: &str,
-    image: &Vec<Vec<Vec3>>,
-) -> io::Result<()> {
+// width and height, or an error if the image is empty or ragged
+fn image_size(image: &[Vec<Vec3>]) -> io::Result<(usize, usize)> {
     let height = image.len();
     if height == 0 {
         return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty image"));
     }
-    
+
     let width = image[0].len();
-    if image.iter().any(|row| row.len() != width) {
+    if width == 0 || image.iter().any(|row| row.len() != width) {
         return Err(io::Error::new(io::ErrorKind::InvalidInput, "Inconsistent row lengths"));
     }
+    Ok((width, height))
+}
+
+pub fn write_to_ppm(
+    filename: &str,
+    image: &Vec<Vec<Vec3>>,
+    post_process: &PostProcess,
+) -> io::Result<()> {
+    let (width, height) = image_size(image)?;
 
     let mut file = File::create(filename)?;
     writeln!(file, "P6")?;
@@ -34,10 +34,10 @@ pub fn write_to_ppm(
 
     for row in image {
         for pixel in row {
-
-            let r = (linear_to_gamma(pixel.x()) * 255.999) as u8;
-            let g = (linear_to_gamma(pixel.y()) * 255.999) as u8;
-            let b = (linear_to_gamma(pixel.z()) * 255.999) as u8;
+            let display = post_process.apply(*pixel);
+            let r = (display.x() * 255.999) as u8;
+            let g = (display.y() * 255.999) as u8;
+            let b = (display.z() * 255.999) as u8;
             file.write_all(&[r, g, b])?;
         }
     }
@@ -45,3 +45,172 @@ pub fn write_to_ppm(
     Ok(())
 }
 
+// the whitespace separated fields at the start of a ppm or pfm file (magic, width, height,
+// then maxval or scale), skipping # comments. also gives back where the last one ended
+pub fn netpbm_header(data: &[u8], count: usize) -> io::Result<(Vec<String>, usize)> {
+    let mut position = 0;
+    let mut header = Vec::new();
+    while header.len() < count {
+        while position < data.len() && data[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        if position < data.len() && data[position] == b'#' {
+            while position < data.len() && data[position] != b'\n' {
+                position += 1;
+            }
+            continue;
+        }
+        let start = position;
+        while position < data.len() && !data[position].is_ascii_whitespace() && data[position] != b'#' {
+            position += 1;
+        }
+        if start == position {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "header ends early"));
+        }
+        header.push(String::from_utf8_lossy(&data[start..position]).into_owned());
+    }
+    Ok((header, position))
+}
+
+// binary ppm (P6), 8 or 16 bits per channel. the values are taken to be srgb encoded, like
+// the ones write_to_ppm puts out, and come back as linear colors
+pub fn read_ppm(filename: &str) -> io::Result<Vec<Vec<Vec3>>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("not a usable ppm: {}", message));
+    let data = fs::read(filename)?;
+
+    let (header, mut position) = netpbm_header(&data, 4).map_err(|_| invalid("header ends early"))?;
+    if header[0] != "P6" {
+        return Err(invalid("only binary P6 files are supported"));
+    }
+    let number = |text: &str| text.parse::<usize>().map_err(|_| invalid("bad number in the header"));
+    let (width, height, max_value) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+    if width == 0 || height == 0 || max_value == 0 || max_value > 65535 {
+        return Err(invalid("bad size or maxval"));
+    }
+    // exactly one whitespace byte between the header and the samples
+    position += 1;
+
+    let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+    let expected = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3 * bytes_per_sample))
+        .ok_or_else(|| invalid("image is too big"))?;
+    if data.len() < position + expected {
+        return Err(invalid("file is truncated"));
+    }
+
+    let samples = &data[position..position + expected];
+    let sample = |i: usize| -> f64 {
+        let value = if bytes_per_sample == 1 {
+            samples[i] as usize
+        } else {
+            (samples[2 * i] as usize) << 8 | samples[2 * i + 1] as usize
+        };
+        srgb_inverse_oetf(value.min(max_value) as f64 / max_value as f64)
+    };
+    Ok((0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let i = 3 * (y * width + x);
+                    Vec3::new(sample(i), sample(i + 1), sample(i + 2))
+                })
+                .collect()
+        })
+        .collect())
+}
+
+pub fn write_to_png(
+    filename: &str,
+    image: &Vec<Vec<Vec3>>,
+    bit_depth: BitDepth,
+    post_process: &PostProcess,
+) -> io::Result<()> {
+    let (width, height) = image_size(image)?;
+
+    let mut samples = Vec::with_capacity(width * height * 3 * 2);
+    for row in image {
+        for pixel in row {
+            let display = post_process.apply(*pixel);
+            for value in [display.x(), display.y(), display.z()] {
+                match bit_depth {
+                    BitDepth::Eight => samples.push((value * 255.999) as u8),
+                    BitDepth::Sixteen => {
+                        samples.extend_from_slice(&((value * 65535.999) as u16).to_be_bytes())
+                    }
+                }
+            }
+        }
+    }
+    png::write(filename, width as u32, height as u32, bit_depth, &samples)
+}
+
+// every format we can write an image out as
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+    // 16 bits per channel
+    Png16,
+    // the rest keep linear floating point radiance
+    Hdr,
+    Pfm,
+    // zip compressed
+    Exr,
+    ExrUncompressed,
+}
+
+impl ImageFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "png16" => Some(ImageFormat::Png16),
+            "hdr" => Some(ImageFormat::Hdr),
+            "pfm" => Some(ImageFormat::Pfm),
+            "exr" => Some(ImageFormat::Exr),
+            "exr-uncompressed" => Some(ImageFormat::ExrUncompressed),
+            _ => None,
+        }
+    }
+
+    // guesses from the file extension, e.g. "out.ppm"
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?;
+        Self::from_name(extension)
+    }
+
+    pub fn names() -> &'static [&'static str] {
+        &["ppm", "png", "png16", "hdr", "pfm", "exr", "exr-uncompressed"]
+    }
+}
+
+// the post process only applies to ppm and png, the float formats get the raw radiance
+pub fn write_image(
+    filename: &str,
+    image: &Vec<Vec<Vec3>>,
+    format: ImageFormat,
+    post_process: &PostProcess,
+) -> io::Result<()> {
+    match format {
+        ImageFormat::Ppm => write_to_ppm(filename, image, post_process),
+        ImageFormat::Png => write_to_png(filename, image, BitDepth::Eight, post_process),
+        ImageFormat::Png16 => write_to_png(filename, image, BitDepth::Sixteen, post_process),
+        ImageFormat::Hdr => {
+            image_size(image)?;
+            hdr::write_to_hdr(filename, image)
+        }
+        ImageFormat::Pfm => {
+            image_size(image)?;
+            hdr::write_to_pfm(filename, image)
+        }
+        ImageFormat::Exr => {
+            image_size(image)?;
+            exr::write_to_exr(filename, image, Compression::Zip)
+        }
+        ImageFormat::ExrUncompressed => {
+            image_size(image)?;
+            exr::write_to_exr(filename, image, Compression::None)
+        }
+    }
+}
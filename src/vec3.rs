@@ -15,19 +15,12 @@ impl Vec3 {
         }
     }
 
-    pub fn random() -> Self {
-        let mut randomizer = Rand::new_with_nanos();
-        Self {
-            vec: [randomizer.next() as f64, randomizer.next() as f64, randomizer.next() as f64]
-        }
-    }
-
-    pub fn random_with_randomizer_and_range(randomizer: &mut Rand, range_min: f32, range_max: f32) -> Self {
+    pub fn random_with_randomizer_and_range(randomizer: &mut Rand, range_min: f64, range_max: f64) -> Self {
         Self {
             vec: [
-                randomizer.next_with_range(range_min, range_max) as f64, 
-                randomizer.next_with_range(range_min, range_max) as f64, 
-                randomizer.next_with_range(range_min, range_max) as f64, 
+                randomizer.next_with_range(range_min, range_max),
+                randomizer.next_with_range(range_min, range_max),
+                randomizer.next_with_range(range_min, range_max),
             ]
         }
     }
@@ -35,18 +28,32 @@ impl Vec3 {
     pub fn random_unit_vector(randomizer: &mut Rand) -> Self {
         loop {
             let rand_vec = Vec3::random_with_randomizer_and_range(randomizer, -1.0, 1.0);
-            if (0.00001 < rand_vec.length_squared() && rand_vec.length_squared() <= 1.0) {
+            if 0.00001 < rand_vec.length_squared() && rand_vec.length_squared() <= 1.0 {
                 return rand_vec.unit_vector()
             }
         }
     }
 
+    // uniform inside the unit disk on the z = 0 plane
+    pub fn random_in_unit_disk(randomizer: &mut Rand) -> Self {
+        loop {
+            let p = Vec3::new(
+                randomizer.next_with_range(-1.0, 1.0),
+                randomizer.next_with_range(-1.0, 1.0),
+                0.0,
+            );
+            if p.length_squared() < 1.0 {
+                return p;
+            }
+        }
+    }
+
     pub fn random_on_hemisphere(normal: &Vec3, randomizer: &mut Rand) -> Self {
         let on_unit_sphere = Vec3::random_unit_vector(randomizer);
         if on_unit_sphere.dot(normal) > 0.0 {
-            return on_unit_sphere;
+            on_unit_sphere
         } else {
-            return Vec3::new(0.0, 0.0, 0.0) - on_unit_sphere;
+            Vec3::new(0.0, 0.0, 0.0) - on_unit_sphere
         }
     }
 
@@ -66,7 +73,7 @@ impl Vec3 {
     }
 
     pub fn near_zero(&self) -> bool {
-        return (self.x() < 0.000001) && (self.y() < 0.000001) &&  (self.z() < 0.000001);
+        (self.x().abs() < 0.000001) && (self.y().abs() < 0.000001) && (self.z().abs() < 0.000001)
     }
 
     #[inline]
@@ -102,6 +109,12 @@ impl Vec3 {
 pub type Point3 = Vec3; // we're basically just making nalgebra atp
 pub type Color3 = Vec3; 
 
+impl From<[f64; 3]> for Vec3 {
+    fn from(vec: [f64; 3]) -> Self {
+        Self { vec }
+    }
+}
+
 impl Index<usize> for Vec3 {
     type Output = f64;
 
@@ -226,7 +239,7 @@ pub fn unit_vector(v: &Vec3) -> Vec3 {
 
 #[inline]
 pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
-    return *v - 2.0 * dot(v, n) * *n;
+    *v - 2.0 * dot(v, n) * *n
 }
 
 #[inline]
@@ -236,3 +249,16 @@ pub fn refract(uv: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
     let r_out_parallel = -f64::sqrt(f64::abs(1.0 - r_out_perp.length_squared())) * *n;
     r_out_perp + r_out_parallel
 }
+
+// two unit vectors that make a right handed frame together with the unit vector n
+// (duff et al. 2017, no branches on which axis n is closest to)
+#[inline]
+pub fn orthonormal_basis(n: &Vec3) -> (Vec3, Vec3) {
+    let sign = 1f64.copysign(n.z());
+    let a = -1.0 / (sign + n.z());
+    let b = n.x() * n.y() * a;
+    (
+        Vec3::new(1.0 + sign * n.x() * n.x() * a, sign * b, -sign * n.x()),
+        Vec3::new(b, sign + n.y() * n.y() * a, -n.y()),
+    )
+}